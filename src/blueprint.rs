@@ -9,22 +9,29 @@ use errors::{OutputSuffix, SpecialProductTerm};
 //
 use crate::{
     chips::Chip,
-    errors::{self, Error, ErrorCode},
+    errors::{self, Error, ErrorCode, LineNum},
     gal::{self, Pin, Term},
-    parser::{Content, Equation, Suffix, LHS},
+    minimiser,
+    parser::{Content, Directives, Equation, Expr, Suffix, Vector, LHS},
 };
 
 // Blueprint stores everything we need to construct the GAL.
+#[derive(Clone)]
 pub struct Blueprint {
     // Data copied straight over from parser::Content.
     pub chip: Chip,
     pub sig: Vec<u8>,
+    pub directives: Directives,
     pub pins: Vec<String>,
+    pub vectors: Vec<Vector>,
     // The Equations, transformed.
     pub olmcs: Vec<OLMC>,
-    // GAL22V10 only:
+    // GAL22V10, GAL18V10 and GAL26CV12 only:
     pub ar: Option<Term>,
     pub sp: Option<Term>,
+    // The lines of the equations with range comparisons, so that the
+    // pin listing can report the rows they end up using.
+    pub range_lines: Vec<LineNum>,
 }
 
 impl Blueprint {
@@ -39,6 +46,7 @@ impl Blueprint {
                 arst: None,
                 aprst: None,
                 feedback: false,
+                inverted: false,
             };
             chip.num_olmcs()
         ];
@@ -46,10 +54,13 @@ impl Blueprint {
         Blueprint {
             chip,
             sig: Vec::new(),
+            directives: Directives::default(),
             pins: Vec::new(),
+            vectors: Vec::new(),
             olmcs,
             ar: None,
             sp: None,
+            range_lines: Vec::new(),
         }
     }
 
@@ -57,7 +68,9 @@ impl Blueprint {
         let mut blueprint = Blueprint::new(content.chip);
 
         blueprint.sig = content.sig.clone();
+        blueprint.directives = content.directives.clone();
         blueprint.pins = content.pins.clone();
+        blueprint.vectors = content.vectors.clone();
 
         // Convert equations into data on the OLMCs.
         for eqn in content.eqns.iter() {
@@ -73,7 +86,7 @@ impl Blueprint {
 
         // Mark all OLMCs that are inputs to other equations as providing feedback.
         // (Note they may actually be used as undriven inputs.)
-        for input in eqn.rhs.iter() {
+        for input in eqn.rhs.pins().iter() {
             if let Some(i) = self.chip.pin_to_olmc(input.pin) {
                 olmcs[i].feedback = true;
             }
@@ -81,6 +94,10 @@ impl Blueprint {
 
         let term = eqn_to_term(self.chip, eqn)?;
 
+        if eqn.rhs.has_range() {
+            self.range_lines.push(eqn.line_num);
+        }
+
         // AR/SP special cases:
         match eqn.lhs {
             LHS::Ar => {
@@ -132,15 +149,93 @@ impl Blueprint {
 
         Ok(())
     }
+
+    // Replace each term with a minimised equivalent, where that saves
+    // rows. Terms that don't shrink are left exactly as written, so
+    // that output stays galasm-compatible wherever possible.
+    pub fn minimise(&mut self) {
+        let chip = self.chip;
+        for olmc in self.olmcs.iter_mut() {
+            if let Some((_, ref mut term)) = olmc.output {
+                minimise_term(chip, term);
+            }
+            for term in [
+                &mut olmc.tri_con,
+                &mut olmc.clock,
+                &mut olmc.arst,
+                &mut olmc.aprst,
+            ]
+            .iter_mut()
+            .filter_map(|term| term.as_mut())
+            {
+                minimise_term(chip, term);
+            }
+        }
+        for term in [&mut self.ar, &mut self.sp]
+            .iter_mut()
+            .filter_map(|term| term.as_mut())
+        {
+            minimise_term(chip, term);
+        }
+    }
+
+    // The rows used by each equation with range comparisons, once
+    // the terms are in their final form. Products moved onto a spare
+    // OLMC keep the equation's line number, so they're counted too.
+    pub fn range_rows(&self) -> Vec<(LineNum, usize)> {
+        let mut terms = Vec::new();
+        for olmc in self.olmcs.iter() {
+            if let Some((_, ref term)) = olmc.output {
+                terms.push(term);
+            }
+            terms.extend(
+                [&olmc.tri_con, &olmc.clock, &olmc.arst, &olmc.aprst]
+                    .iter()
+                    .filter_map(|term| term.as_ref()),
+            );
+        }
+        terms.extend([&self.ar, &self.sp].iter().filter_map(|term| term.as_ref()));
+
+        self.range_lines
+            .iter()
+            .map(|&line_num| {
+                let rows = terms
+                    .iter()
+                    .filter(|term| term.line_num == line_num)
+                    .map(|term| term.pins.len())
+                    .sum();
+                (line_num, rows)
+            })
+            .collect()
+    }
+}
+
+// Does the term mention VCC or GND as an input? Such terms are left
+// alone by rewriting passes, so that the misuse gets reported later.
+pub(crate) fn uses_power(chip: Chip, term: &Term) -> bool {
+    term.pins
+        .iter()
+        .flatten()
+        .any(|pin| pin.pin == chip.vcc_pin() || pin.pin == chip.gnd_pin())
+}
+
+fn minimise_term(chip: Chip, term: &mut Term) {
+    if uses_power(chip, term) {
+        return;
+    }
+
+    let minimised = minimiser::minimise(term);
+    if minimised.pins.len() < term.pins.len() {
+        *term = minimised;
+    }
 }
 
 // Convert an Equation, which is close to the input syntax, into a
 // Term, which is close to the fuse map representation.
-fn eqn_to_term(chip: Chip, eqn: &Equation) -> Result<Term, ErrorCode> {
+pub(crate) fn eqn_to_term(chip: Chip, eqn: &Equation) -> Result<Term, ErrorCode> {
     // Special case for constant true or false.
-    if eqn.rhs.len() == 1 {
-        let pin = &eqn.rhs[0];
-        if pin.pin == chip.num_pins() {
+    if let Expr::Pin(pin) = &eqn.rhs {
+        if pin.pin == chip.vcc_pin() {
             // VCC
             if pin.neg {
                 return Err(ErrorCode::InvertedPower {
@@ -149,7 +244,7 @@ fn eqn_to_term(chip: Chip, eqn: &Equation) -> Result<Term, ErrorCode> {
                 });
             }
             return Ok(gal::true_term(eqn.line_num));
-        } else if pin.pin == chip.num_pins() / 2 {
+        } else if pin.pin == chip.gnd_pin() {
             // GND
             if pin.neg {
                 return Err(ErrorCode::InvertedPower {
@@ -161,23 +256,162 @@ fn eqn_to_term(chip: Chip, eqn: &Equation) -> Result<Term, ErrorCode> {
         }
     }
 
-    // Create a list of OR'd terms, each team being a group of AND'd terms.
-    let mut ors = Vec::new();
-    let mut ands = Vec::new();
+    // Expanding XORs and nested products can blow up exponentially,
+    // so give up once there are far more products than the chip has
+    // rows, rather than waiting for the minimiser to be overwhelmed.
+    let limit = chip.num_rows() * EXPANSION_FACTOR;
+    Ok(Term {
+        line_num: eqn.line_num,
+        pins: expr_to_sop(&eqn.rhs, false, limit)?,
+    })
+}
+
+// How many times the chip's rows an equation may expand to before
+// it's rejected. Minimisation can shrink an equation, but not by more
+// than this in any sensible design.
+const EXPANSION_FACTOR: usize = 4;
 
-    for (pin, is_or) in eqn.rhs.iter().zip(eqn.is_or.iter()) {
-        if *is_or {
-            ors.push(ands);
-            ands = Vec::new();
+// Flatten an expression into a list of OR'd terms, each term being a
+// group of AND'd pins. 'neg' requests the complement of the
+// expression, which is pushed down to the pins using De Morgan's laws.
+//
+// No simplification is done, so that a flat sum-of-products comes out
+// exactly as it was written. Expansions of more than 'limit' products
+// are rejected.
+fn expr_to_sop(expr: &Expr, neg: bool, limit: usize) -> Result<Vec<Vec<Pin>>, ErrorCode> {
+    let sop = match expr {
+        Expr::Pin(pin) => vec![vec![Pin {
+            pin: pin.pin,
+            neg: pin.neg != neg,
+        }]],
+        Expr::Not(expr) => expr_to_sop(expr, !neg, limit)?,
+        Expr::And(exprs) if !neg => sop_and(sops(exprs, false, limit)?, limit)?,
+        Expr::Or(exprs) if neg => sop_and(sops(exprs, true, limit)?, limit)?,
+        Expr::And(exprs) | Expr::Or(exprs) => sops(exprs, neg, limit)?.concat(),
+        // A $ B is A * /B + /A * B, and its complement is A * B + /A * /B.
+        Expr::Xor(lhs, rhs) => {
+            let mut sop = sop_and(
+                vec![
+                    expr_to_sop(lhs, false, limit)?,
+                    expr_to_sop(rhs, !neg, limit)?,
+                ],
+                limit,
+            )?;
+            sop.extend(sop_and(
+                vec![
+                    expr_to_sop(lhs, true, limit)?,
+                    expr_to_sop(rhs, neg, limit)?,
+                ],
+                limit,
+            )?);
+            sop
         }
-        ands.push(*pin);
+        Expr::Range { pins, lo, hi } => range_to_sop(pins, *lo, *hi, neg),
+    };
+    check_limit(sop.len(), limit)?;
+    Ok(sop)
+}
+
+fn sops(exprs: &[Expr], neg: bool, limit: usize) -> Result<Vec<Vec<Vec<Pin>>>, ErrorCode> {
+    exprs
+        .iter()
+        .map(|expr| expr_to_sop(expr, neg, limit))
+        .collect()
+}
+
+fn check_limit(seen: usize, limit: usize) -> Result<(), ErrorCode> {
+    if seen > limit {
+        return Err(ErrorCode::ExpansionTooBig { max: limit, seen });
     }
-    ors.push(ands);
+    Ok(())
+}
 
-    Ok(Term {
-        line_num: eqn.line_num,
-        pins: ors,
-    })
+// The products that are true for values from 'lo' to 'hi' on the
+// pins, most significant first, or for the values outside the range
+// with 'neg'.
+fn range_to_sop(pins: &[Pin], lo: u64, hi: u64, neg: bool) -> Vec<Vec<Pin>> {
+    let max = u64::MAX >> (64 - pins.len());
+    let inside = vec![(lo, hi)];
+    let mut outside = Vec::new();
+    if lo > 0 {
+        outside.push((0, lo - 1));
+    }
+    if hi < max {
+        outside.push((hi + 1, max));
+    }
+    let (wanted, unwanted) = if neg {
+        (outside, inside)
+    } else {
+        (inside, outside)
+    };
+
+    // Merging the blocks of the range is usually best, but where the
+    // range covers nearly everything, the complement of what's left
+    // over can be smaller.
+    let direct = minimiser::minimise(&range_blocks(pins, &wanted));
+    let complemented = minimiser::complement(&range_blocks(pins, &unwanted));
+    if complemented.pins.len() < direct.pins.len() {
+        complemented.pins
+    } else {
+        direct.pins
+    }
+}
+
+// Split the ranges into aligned power-of-two blocks, which are one
+// product each.
+fn range_blocks(pins: &[Pin], ranges: &[(u64, u64)]) -> Term {
+    let width = pins.len();
+    let mut products = Vec::new();
+    for &(mut from, to) in ranges.iter() {
+        loop {
+            // Find the biggest block starting at 'from' that fits.
+            let mut size = 0;
+            while size < width && from % (2 << size) == 0 && from + (2 << size) - 1 <= to {
+                size += 1;
+            }
+            // The block's fixed bits are the ones above its size.
+            products.push(
+                pins[..width - size]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pin)| Pin {
+                        pin: pin.pin,
+                        neg: pin.neg == (from >> (width - 1 - i) & 1 != 0),
+                    })
+                    .collect::<Vec<Pin>>(),
+            );
+            let next = from + (1 << size);
+            if next > to {
+                break;
+            }
+            from = next;
+        }
+    }
+
+    Term {
+        line_num: 0,
+        pins: products,
+    }
+}
+
+// AND together a set of sums-of-products, distributing the ANDs over
+// the ORs. The size is checked before each step is expanded.
+fn sop_and(sops: Vec<Vec<Vec<Pin>>>, limit: usize) -> Result<Vec<Vec<Pin>>, ErrorCode> {
+    let mut acc = vec![Vec::new()];
+    for sop in sops.into_iter() {
+        check_limit(acc.len().saturating_mul(sop.len()), limit)?;
+        acc = acc
+            .iter()
+            .flat_map(|lhs| {
+                sop.iter().map(move |rhs| {
+                    let mut product = lhs.clone();
+                    product.extend_from_slice(rhs);
+                    product
+                })
+            })
+            .collect();
+    }
+    Ok(acc)
 }
 
 ////////////////////////////////////////////////////////////////////////
@@ -193,6 +427,9 @@ pub struct OLMC {
     pub arst: Option<gal::Term>,
     pub aprst: Option<gal::Term>,
     pub feedback: bool,
+    // Set if the fitter implemented the output as the complement of
+    // its equation, with the opposite polarity.
+    pub inverted: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -228,7 +465,7 @@ impl OLMC {
             });
         }
 
-        if self.tri_con != None {
+        if self.tri_con.is_some() {
             return Err(ErrorCode::RepeatedControl {
                 suffix: OutputSuffix::E,
             });
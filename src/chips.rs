@@ -21,12 +21,20 @@ pub struct Bounds {
 // parameters. Some things vary across the differing GAL{16,20}V8 modes,
 // and those things can't be queried on 'Chip'. Look them up on 'GAL'
 // instead.
+//
+// The Atmel parts are fuse-compatible with the GALs they replace, and
+// 'arch' maps them to the GAL to match on for their layout.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Chip {
     GAL16V8,
     GAL20V8,
     GAL22V10,
     GAL20RA10,
+    GAL18V10,
+    GAL26CV12,
+    ATF16V8B,
+    ATF20V8B,
+    ATF22V10C,
 }
 
 // 'ChipData' stores these per-chip-type parameters, so that the
@@ -36,6 +44,9 @@ struct ChipData {
     name: &'static str,
     // Number of pins on the package.
     num_pins: usize,
+    // Power pins.
+    gnd_pin: usize,
+    vcc_pin: usize,
     // Size of the main fuse array, in number of rows (each row
     // represents an OR-term).
     num_rows: usize,
@@ -44,73 +55,153 @@ struct ChipData {
     num_cols: usize,
     // Total size of the bitstream.
     total_size: usize,
-    // Range of pins that are backed by OLMCs (and can act as
-    // programmable outputs).
-    min_olmc_pin: usize,
-    max_olmc_pin: usize,
+    // Pins that are backed by OLMCs (and can act as programmable
+    // outputs), in OLMC number order.
+    olmc_pins: &'static [usize],
     // Mapping from OLMC number to starting row number in the fuse grid.
     olmc_map: &'static [i32],
-    // NB: Number of rows per OLMC depends on the GAL type, and isn't in
-    // ChipData.
+    // Number of rows for each OLMC.
+    olmc_sizes: &'static [i32],
 }
 
 const GAL16V8_DATA: ChipData = ChipData {
     name: "GAL16V8",
     num_pins: 20,
+    gnd_pin: 10,
+    vcc_pin: 20,
     num_rows: 64,
     num_cols: 32,
     total_size: 2194,
-    min_olmc_pin: 12,
-    max_olmc_pin: 19,
+    olmc_pins: &OLMC_PINS_16V8,
     olmc_map: &OLMC_ROWS_XXV8,
+    olmc_sizes: &OLMC_SIZE_XXV8,
 };
 
 const GAL20V8_DATA: ChipData = ChipData {
     name: "GAL20V8",
     num_pins: 24,
+    gnd_pin: 12,
+    vcc_pin: 24,
     num_rows: 64,
     num_cols: 40,
     total_size: 2706,
-    min_olmc_pin: 15,
-    max_olmc_pin: 22,
+    olmc_pins: &OLMC_PINS_20V8,
     olmc_map: &OLMC_ROWS_XXV8,
+    olmc_sizes: &OLMC_SIZE_XXV8,
 };
 
 const GAL22V10_DATA: ChipData = ChipData {
     name: "GAL22V10",
     num_pins: 24,
+    gnd_pin: 12,
+    vcc_pin: 24,
     num_rows: 132,
     num_cols: 44,
     total_size: 5892,
-    min_olmc_pin: 14,
-    max_olmc_pin: 23,
+    olmc_pins: &OLMC_PINS_XXV10,
     olmc_map: &OLMC_ROWS_22V10,
+    olmc_sizes: &OLMC_SIZE_22V10,
+};
+
+const GAL18V10_DATA: ChipData = ChipData {
+    name: "GAL18V10",
+    num_pins: 20,
+    gnd_pin: 10,
+    vcc_pin: 20,
+    num_rows: 96,
+    num_cols: 36,
+    total_size: 3540,
+    olmc_pins: &OLMC_PINS_18V10,
+    olmc_map: &OLMC_ROWS_18V10,
+    olmc_sizes: &OLMC_SIZE_18V10,
+};
+
+// The GAL26CV12 has its power pins in the middle of each side.
+const GAL26CV12_DATA: ChipData = ChipData {
+    name: "GAL26CV12",
+    num_pins: 28,
+    gnd_pin: 21,
+    vcc_pin: 7,
+    num_rows: 122,
+    num_cols: 52,
+    total_size: 6432,
+    olmc_pins: &OLMC_PINS_26CV12,
+    olmc_map: &OLMC_ROWS_26CV12,
+    olmc_sizes: &OLMC_SIZE_26CV12,
 };
 
 const GAL20RA10_DATA: ChipData = ChipData {
     name: "GAL20RA10",
     num_pins: 24,
+    gnd_pin: 12,
+    vcc_pin: 24,
     num_rows: 80,
     num_cols: 40,
     total_size: 3274,
-    min_olmc_pin: 14,
-    max_olmc_pin: 23,
+    olmc_pins: &OLMC_PINS_XXV10,
     olmc_map: &OLMC_ROWS_20RA10,
+    olmc_sizes: &OLMC_SIZE_20RA10,
+};
+
+// The Atmel parts only differ in name. Their fuse counts match the
+// Lattice parts: the ATF22V10C has the same 64-bit UES after the S0/S1
+// bits, for 5892 fuses in all. It can also be programmed from the
+// 5828-fuse map of a PAL22V10, which has no UES (see
+// 'has_optional_ues').
+const ATF16V8B_DATA: ChipData = ChipData {
+    name: "ATF16V8B",
+    ..GAL16V8_DATA
+};
+
+const ATF20V8B_DATA: ChipData = ChipData {
+    name: "ATF20V8B",
+    ..GAL20V8_DATA
+};
+
+const ATF22V10C_DATA: ChipData = ChipData {
+    name: "ATF22V10C",
+    ..GAL22V10_DATA
 };
 
 // These constants are used to get the fuse row bounds associated with
 // the OLMCs.
 
-// Number of rows for each OLMC in the 22V10's fuse table (only 22V10
-// is non-uniform).
+// OLMC pins. The GAL18V10's and GAL26CV12's are split by the GND pin.
+const OLMC_PINS_16V8: [usize; 8] = [12, 13, 14, 15, 16, 17, 18, 19];
+const OLMC_PINS_20V8: [usize; 8] = [15, 16, 17, 18, 19, 20, 21, 22];
+const OLMC_PINS_XXV10: [usize; 10] = [14, 15, 16, 17, 18, 19, 20, 21, 22, 23];
+const OLMC_PINS_18V10: [usize; 10] = [9, 11, 12, 13, 14, 15, 16, 17, 18, 19];
+const OLMC_PINS_26CV12: [usize; 12] = [15, 16, 17, 18, 19, 20, 22, 23, 24, 25, 26, 27];
+
+// Number of rows for each OLMC in the fuse table. The 22V10, 18V10
+// and 26CV12 are non-uniform, and include the tristate enable row.
 const OLMC_SIZE_22V10: [i32; 10] = [9, 11, 13, 15, 17, 17, 15, 13, 11, 9];
+const OLMC_SIZE_18V10: [i32; 10] = [9, 9, 9, 9, 11, 11, 9, 9, 9, 9];
+const OLMC_SIZE_26CV12: [i32; 12] = [9, 9, 9, 9, 11, 13, 13, 11, 9, 9, 9, 9];
 // And for all the other chips, they have 8 rows per OLMC:
-const OLMC_SIZE_DEFAULT: i32 = 8;
+const OLMC_SIZE_XXV8: [i32; 8] = [8; 8];
+const OLMC_SIZE_20RA10: [i32; 10] = [8; 10];
 
 // Map OLMC number to starting row within the fuse table
 const OLMC_ROWS_XXV8: [i32; 8] = [56, 48, 40, 32, 24, 16, 8, 0];
 const OLMC_ROWS_22V10: [i32; 10] = [122, 111, 98, 83, 66, 49, 34, 21, 10, 1];
 const OLMC_ROWS_20RA10: [i32; 10] = [72, 64, 56, 48, 40, 32, 24, 16, 8, 0];
+const OLMC_ROWS_18V10: [i32; 10] = [86, 77, 68, 59, 48, 37, 28, 19, 10, 1];
+const OLMC_ROWS_26CV12: [i32; 12] = [112, 103, 94, 85, 74, 61, 48, 37, 28, 19, 10, 1];
+
+// Every chip, in the order they're tried when identifying a JEDEC
+// file, so that the GALs come before the compatible parts.
+const ALL_CHIPS: [Chip; 9] = [
+    Chip::GAL16V8,
+    Chip::GAL20V8,
+    Chip::GAL22V10,
+    Chip::GAL20RA10,
+    Chip::GAL18V10,
+    Chip::GAL26CV12,
+    Chip::ATF16V8B,
+    Chip::ATF20V8B,
+    Chip::ATF22V10C,
+];
 
 impl Chip {
     pub fn from_name(name: &str) -> Result<Chip, ErrorCode> {
@@ -119,20 +210,58 @@ impl Chip {
             "GAL20V8" => Ok(Chip::GAL20V8),
             "GAL22V10" => Ok(Chip::GAL22V10),
             "GAL20RA10" => Ok(Chip::GAL20RA10),
+            "GAL18V10" => Ok(Chip::GAL18V10),
+            "GAL26CV12" | "GAL26V12" => Ok(Chip::GAL26CV12),
+            "ATF16V8B" => Ok(Chip::ATF16V8B),
+            "ATF20V8B" => Ok(Chip::ATF20V8B),
+            "ATF22V10C" | "ATF22V10CQ" => Ok(Chip::ATF22V10C),
             _ => Err(ErrorCode::BadGALType),
         }
     }
 
+    // The GAL with the same fuse layout.
+    pub fn arch(&self) -> Chip {
+        match self {
+            Chip::ATF16V8B => Chip::GAL16V8,
+            Chip::ATF20V8B => Chip::GAL20V8,
+            Chip::ATF22V10C => Chip::GAL22V10,
+            chip => *chip,
+        }
+    }
+
+    // Identify the chip from the size of its bitstream, as given in a
+    // JEDEC file. This gives the GAL, rather than a compatible part.
+    pub fn from_total_size(size: usize) -> Option<Chip> {
+        ALL_CHIPS
+            .iter()
+            .copied()
+            .find(|chip| chip.total_size() == size)
+    }
+
+    // The most fuses any supported chip has.
+    pub fn max_total_size() -> usize {
+        ALL_CHIPS
+            .iter()
+            .map(|chip| chip.total_size())
+            .max()
+            .unwrap()
+    }
+
     fn get_chip_data(&self) -> &ChipData {
         match self {
             Chip::GAL16V8 => &GAL16V8_DATA,
             Chip::GAL20V8 => &GAL20V8_DATA,
             Chip::GAL22V10 => &GAL22V10_DATA,
             Chip::GAL20RA10 => &GAL20RA10_DATA,
+            Chip::GAL18V10 => &GAL18V10_DATA,
+            Chip::GAL26CV12 => &GAL26CV12_DATA,
+            Chip::ATF16V8B => &ATF16V8B_DATA,
+            Chip::ATF20V8B => &ATF20V8B_DATA,
+            Chip::ATF22V10C => &ATF22V10C_DATA,
         }
     }
 
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &'static str {
         self.get_chip_data().name
     }
 
@@ -140,10 +269,22 @@ impl Chip {
         self.get_chip_data().num_pins
     }
 
+    pub fn gnd_pin(&self) -> usize {
+        self.get_chip_data().gnd_pin
+    }
+
+    pub fn vcc_pin(&self) -> usize {
+        self.get_chip_data().vcc_pin
+    }
+
     pub fn num_cols(&self) -> usize {
         self.get_chip_data().num_cols
     }
 
+    pub fn num_rows(&self) -> usize {
+        self.get_chip_data().num_rows
+    }
+
     pub fn logic_size(&self) -> usize {
         let data = self.get_chip_data();
         data.num_rows * data.num_cols
@@ -153,34 +294,54 @@ impl Chip {
         self.get_chip_data().total_size
     }
 
+    // Whether the 64-bit user electronic signature can be left out of
+    // the bitstream, with "%UES OFF". The ATF22V10C takes the 5828-fuse
+    // map without it, as used for a PAL22V10.
+    pub fn has_optional_ues(&self) -> bool {
+        *self == Chip::ATF22V10C
+    }
+
     pub fn pin_to_olmc(&self, pin: usize) -> Option<usize> {
-        let data = self.get_chip_data();
-        if data.min_olmc_pin <= pin && pin <= data.max_olmc_pin {
-            Some(pin - data.min_olmc_pin)
-        } else {
-            None
-        }
+        self.get_chip_data()
+            .olmc_pins
+            .iter()
+            .position(|olmc_pin| *olmc_pin == pin)
+    }
+
+    pub fn olmc_to_pin(&self, olmc_num: usize) -> usize {
+        self.get_chip_data().olmc_pins[olmc_num]
     }
 
     // Pin number of last OLMC'd output.
     pub fn last_olmc(&self) -> usize {
-        self.get_chip_data().max_olmc_pin
+        self.olmc_to_pin(self.num_olmcs() - 1)
     }
 
     // Count of OLMCs
     pub fn num_olmcs(&self) -> usize {
-        let data = self.get_chip_data();
-        data.max_olmc_pin - data.min_olmc_pin + 1
+        self.get_chip_data().olmc_pins.len()
     }
 
-    // Not everything is easiest driven off a table...
     pub fn num_rows_for_olmc(&self, olmc_num: usize) -> usize {
-        if *self == Chip::GAL22V10 {
-            // Only 22V10 has non-uniform-sized OLMCs.
-            OLMC_SIZE_22V10[olmc_num] as usize
-        } else {
-            OLMC_SIZE_DEFAULT as usize
-        }
+        self.get_chip_data().olmc_sizes[olmc_num] as usize
+    }
+
+    // The GAL22V10, GAL18V10 and GAL26CV12 share an OLMC design, with
+    // S0/S1 configuration bits, and global AR and SP terms in the
+    // first and last rows of the fuse map.
+    pub fn has_arsp(&self) -> bool {
+        matches!(
+            self.arch(),
+            Chip::GAL22V10 | Chip::GAL18V10 | Chip::GAL26CV12
+        )
+    }
+
+    pub fn ar_row(&self) -> usize {
+        0
+    }
+
+    pub fn sp_row(&self) -> usize {
+        self.get_chip_data().num_rows - 1
     }
 
     pub fn get_bounds(&self, olmc_num: usize) -> Bounds {
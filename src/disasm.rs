@@ -0,0 +1,423 @@
+//
+// disasm.rs: Disassembler
+//
+// Given a GAL structure (typically read from a JEDEC file by
+// jedec.rs), this module recovers a set of equations, written out as
+// a .pld file that galette can assemble back into the same fuse map.
+//
+// It's the inverse of gal_builder.rs, and relies on the same
+// knowledge of how each chip's rows and mode bits are laid out.
+//
+
+use std::fmt::Write;
+
+use crate::{
+    chips::Chip,
+    gal::{Mode, Pin, Term, GAL},
+};
+
+// An equation recovered from the fuse map, with the left-hand side
+// described by pin number and suffix, as names are assigned later.
+struct Equation {
+    lhs: Target,
+    term: Term,
+}
+
+enum Target {
+    Pin {
+        pin: usize,
+        neg: bool,
+        suffix: &'static str,
+    },
+    Special(&'static str),
+}
+
+pub fn disassemble(gal: &GAL) -> String {
+    let chip = gal.chip;
+
+    let mut eqns = Vec::new();
+    for olmc_num in 0..chip.num_olmcs() {
+        match chip {
+            Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => {
+                disasm_galxv8(gal, olmc_num, &mut eqns)
+            }
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+                disasm_gal22v10(gal, olmc_num, &mut eqns)
+            }
+            Chip::GAL20RA10 => disasm_gal20ra10(gal, olmc_num, &mut eqns),
+        }
+    }
+
+    // AR and SP on the 22V10, 18V10 and 26CV12 are single rows at either end
+    // of the fuse map.
+    if chip.has_arsp() {
+        for (name, row) in [("AR", chip.ar_row()), ("SP", chip.sp_row())].iter() {
+            let term = decode_term(gal, *row, 1);
+            if !term.pins.is_empty() {
+                eqns.push(Equation {
+                    lhs: Target::Special(name),
+                    term,
+                });
+            }
+        }
+    }
+
+    let pin_names = name_pins(gal, &eqns);
+    make_pld(gal, &pin_names, &eqns)
+}
+
+////////////////////////////////////////////////////////////////////////
+// Chip-specific decoding of the OLMCs.
+//
+
+fn disasm_galxv8(gal: &GAL, olmc_num: usize, eqns: &mut Vec<Equation>) {
+    let chip = gal.chip;
+    let pin = chip.olmc_to_pin(olmc_num);
+    let (xor, ac1) = mode_bits(gal, olmc_num);
+    let start_row = chip.get_bounds(olmc_num).start_row;
+    let num_rows = chip.num_rows_for_olmc(olmc_num);
+
+    match gal.get_mode() {
+        Mode::Simple => {
+            // AC1 set means the pin is an input.
+            if !ac1 {
+                let term = decode_term(gal, start_row, num_rows);
+                add_output(eqns, pin, xor, "", term);
+            }
+        }
+        Mode::Complex => {
+            // Every output is tristate, with the enable in the first
+            // row. Writing them as such keeps us in complex mode.
+            if ac1 {
+                add_tristate(gal, eqns, pin, xor, start_row, num_rows, ".T");
+            }
+        }
+        Mode::Registered => {
+            if ac1 {
+                add_tristate(gal, eqns, pin, xor, start_row, num_rows, ".T");
+            } else {
+                let term = decode_term(gal, start_row, num_rows);
+                add_output(eqns, pin, xor, ".R", term);
+            }
+        }
+    }
+}
+
+fn disasm_gal22v10(gal: &GAL, olmc_num: usize, eqns: &mut Vec<Equation>) {
+    let chip = gal.chip;
+    let pin = chip.olmc_to_pin(olmc_num);
+    // S0 is stored in 'xor', S1 in 'ac1'.
+    let (s0, s1) = mode_bits(gal, olmc_num);
+    let start_row = chip.get_bounds(olmc_num).start_row;
+    let num_rows = chip.num_rows_for_olmc(olmc_num);
+
+    if s1 {
+        add_tristate(gal, eqns, pin, s0, start_row, num_rows, "");
+    } else {
+        let enable = decode_term(gal, start_row, 1);
+        let term = decode_term(gal, start_row + 1, num_rows - 1);
+        if add_output(eqns, pin, s0, ".R", term) && !is_true(&enable) {
+            add_eqn(eqns, pin, false, ".E", enable);
+        }
+    }
+}
+
+fn disasm_gal20ra10(gal: &GAL, olmc_num: usize, eqns: &mut Vec<Equation>) {
+    let chip = gal.chip;
+    let pin = chip.olmc_to_pin(olmc_num);
+    let (s0, _) = mode_bits(gal, olmc_num);
+    let start_row = chip.get_bounds(olmc_num).start_row;
+    let num_rows = chip.num_rows_for_olmc(olmc_num);
+
+    // Rows are: tristate enable, CLK, ARST, APRST, main term.
+    let enable = decode_term(gal, start_row, 1);
+    let clock = decode_term(gal, start_row + 1, 1);
+    let arst = decode_term(gal, start_row + 2, 1);
+    let aprst = decode_term(gal, start_row + 3, 1);
+    let term = decode_term(gal, start_row + 4, num_rows - 4);
+
+    // The register is bypassed if ARST and APRST are both true.
+    if is_true(&arst) && is_true(&aprst) {
+        let suffix = if is_true(&enable) { "" } else { ".T" };
+        if add_output(eqns, pin, s0, suffix, term) && !is_true(&enable) {
+            add_eqn(eqns, pin, false, ".E", enable);
+        }
+    } else if add_output(eqns, pin, s0, ".R", term) {
+        if !is_true(&enable) {
+            add_eqn(eqns, pin, false, ".E", enable);
+        }
+        add_eqn(eqns, pin, false, ".CLK", clock);
+        if !arst.pins.is_empty() {
+            add_eqn(eqns, pin, false, ".ARST", arst);
+        }
+        if !aprst.pins.is_empty() {
+            add_eqn(eqns, pin, false, ".APRST", aprst);
+        }
+    }
+}
+
+// Add a tristate output, with the enable term in the first row. If
+// it's always enabled, it's written with 'enabled_suffix'.
+fn add_tristate(
+    gal: &GAL,
+    eqns: &mut Vec<Equation>,
+    pin: usize,
+    active_high: bool,
+    start_row: usize,
+    num_rows: usize,
+    enabled_suffix: &'static str,
+) {
+    let enable = decode_term(gal, start_row, 1);
+    let term = decode_term(gal, start_row + 1, num_rows - 1);
+
+    // An output that's never enabled, with no equation, is an input
+    // (or unused).
+    if enable.pins.is_empty() && term.pins.is_empty() && !active_high {
+        return;
+    }
+
+    if is_true(&enable) {
+        add_eqn(eqns, pin, !active_high, enabled_suffix, term);
+    } else {
+        add_eqn(eqns, pin, !active_high, ".T", term);
+        add_eqn(eqns, pin, false, ".E", enable);
+    }
+}
+
+// Add the main equation for an output, returning false if it's an
+// unused output (constant false with no inversion, as gal_builder
+// leaves unused OLMCs).
+fn add_output(
+    eqns: &mut Vec<Equation>,
+    pin: usize,
+    active_high: bool,
+    suffix: &'static str,
+    term: Term,
+) -> bool {
+    if term.pins.is_empty() && !active_high {
+        return false;
+    }
+
+    add_eqn(eqns, pin, !active_high, suffix, term);
+    true
+}
+
+fn add_eqn(eqns: &mut Vec<Equation>, pin: usize, neg: bool, suffix: &'static str, term: Term) {
+    eqns.push(Equation {
+        lhs: Target::Pin { pin, neg, suffix },
+        term,
+    });
+}
+
+////////////////////////////////////////////////////////////////////////
+// Helper functions for reading the fuses.
+//
+
+// The XOR (or S0) and AC1 (or S1) bits are stored in reverse order to
+// the OLMCs.
+fn mode_bits(gal: &GAL, olmc_num: usize) -> (bool, bool) {
+    let idx = gal.chip.num_olmcs() - 1 - olmc_num;
+    (gal.xor[idx], gal.ac1[idx])
+}
+
+// Read a set of rows back into a term. Rows that have had all their
+// fuses cleared, or have been disabled by the PT bits, are dropped.
+// Other rows are kept exactly as they are, even if they contain both
+// an input and its negation, so that the term reassembles into the
+// same fuses.
+fn decode_term(gal: &GAL, start_row: usize, num_rows: usize) -> Term {
+    let chip = gal.chip;
+    let row_len = chip.num_cols();
+
+    let mut pins = Vec::new();
+    for row in start_row..start_row + num_rows {
+        if (chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8) && !gal.pt[row] {
+            continue;
+        }
+
+        let fuses = &gal.fuses[row * row_len..(row + 1) * row_len];
+        if fuses.iter().all(|fuse| !fuse) {
+            continue;
+        }
+
+        let product = (0..row_len)
+            .filter(|col| !fuses[*col])
+            .filter_map(|col| {
+                gal.column_to_pin(col).map(|pin| Pin {
+                    pin,
+                    // Undo the flip applied to registered feedback
+                    // on the 22V10.
+                    neg: (col % 2 == 1) != gal.needs_flip(pin),
+                })
+            })
+            .collect::<Vec<Pin>>();
+        pins.push(product);
+    }
+
+    Term { line_num: 0, pins }
+}
+
+// Is the term constant true?
+fn is_true(term: &Term) -> bool {
+    term.pins.iter().any(|product| product.is_empty())
+}
+
+////////////////////////////////////////////////////////////////////////
+// Writing out the .pld file.
+//
+
+// Name the pins. Any pin used in an equation is named after its pin
+// number, and the rest are left unconnected, apart from the clock
+// and output enable pins.
+fn name_pins(gal: &GAL, eqns: &[Equation]) -> Vec<String> {
+    let chip = gal.chip;
+    let num_pins = chip.num_pins();
+
+    let mut used = vec![false; num_pins + 1];
+    for eqn in eqns.iter() {
+        if let Target::Pin { pin, .. } = eqn.lhs {
+            used[pin] = true;
+        }
+        for pin in eqn.term.pins.iter().flatten() {
+            used[pin.pin] = true;
+        }
+    }
+
+    let registered = eqns
+        .iter()
+        .any(|eqn| matches!(eqn.lhs, Target::Pin { suffix: ".R", .. }));
+
+    (1..=num_pins)
+        .map(|pin| {
+            if pin == chip.vcc_pin() {
+                String::from("VCC")
+            } else if pin == chip.gnd_pin() {
+                String::from("GND")
+            } else if used[pin] {
+                format!("P{}", pin)
+            } else {
+                String::from(match chip {
+                    Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B
+                        if gal.get_mode() == Mode::Registered =>
+                    {
+                        match pin {
+                            1 => "Clock",
+                            11 if chip.arch() == Chip::GAL16V8 => "OE",
+                            13 if chip.arch() == Chip::GAL20V8 => "OE",
+                            _ => "NC",
+                        }
+                    }
+                    Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12
+                        if pin == 1 && registered =>
+                    {
+                        "Clock"
+                    }
+                    Chip::GAL20RA10 if pin == 1 => "PL",
+                    Chip::GAL20RA10 if pin == 13 => "OE",
+                    _ => "NC",
+                })
+            }
+        })
+        .collect()
+}
+
+fn make_pld(gal: &GAL, pin_names: &[String], eqns: &[Equation]) -> String {
+    let chip = gal.chip;
+    let num_pins = chip.num_pins();
+    let mut buf = String::new();
+
+    let _ = writeln!(buf, "{}", chip.name());
+
+    // Signature, dropping trailing zero bytes and anything
+    // unprintable.
+    let mut sig = (0..gal.sig.len() / 8)
+        .map(|i| (0..8).fold(0u8, |acc, j| (acc << 1) | gal.sig[i * 8 + j] as u8))
+        .collect::<Vec<u8>>();
+    let value = sig.iter().fold(0u64, |acc, c| (acc << 8) | *c as u64);
+    while sig.last() == Some(&0) {
+        sig.pop();
+    }
+    let is_printable = |c: &u8| c.is_ascii_graphic() || *c == b' ';
+    let printable = sig.iter().all(is_printable);
+    let sig = sig
+        .iter()
+        .map(|c| if is_printable(c) { *c as char } else { '?' })
+        .collect::<String>();
+    let _ = writeln!(buf, "{}", sig);
+
+    // The mode is normally picked based on the equations, but that
+    // doesn't work for outputs that don't need the mode they're in.
+    if chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8 {
+        let uses_suffix = |wanted| {
+            eqns.iter()
+                .any(|eqn| matches!(eqn.lhs, Target::Pin { suffix, .. } if suffix == wanted))
+        };
+        match gal.get_mode() {
+            Mode::Complex if !uses_suffix(".T") => buf.push_str("%MODE COMPLEX\n"),
+            Mode::Registered if !uses_suffix(".R") => buf.push_str("%MODE REGISTERED\n"),
+            _ => {}
+        }
+    }
+    // A signature that can't be written as text is given in full.
+    if !printable {
+        let _ = writeln!(buf, "%SIG 0x{:016X}", value);
+    }
+    if gal.sig.is_empty() {
+        buf.push_str("%UES OFF\n");
+    }
+    buf.push('\n');
+
+    for names in pin_names.chunks(num_pins / 2) {
+        let line = names
+            .iter()
+            .map(|name| format!("{:<6}", name))
+            .collect::<String>();
+        let _ = writeln!(buf, "{}", line.trim_end());
+    }
+    buf.push('\n');
+
+    for eqn in eqns.iter() {
+        let lhs = match eqn.lhs {
+            Target::Pin { pin, neg, suffix } => format!(
+                "{}{}{}",
+                if neg { "/" } else { "" },
+                pin_names[pin - 1],
+                suffix
+            ),
+            Target::Special(name) => String::from(name),
+        };
+        let _ = writeln!(buf, "{} = {}", lhs, term_to_string(pin_names, &eqn.term));
+    }
+
+    buf.push_str("\nDESCRIPTION\n\nDisassembled from a JEDEC file by galette.\n");
+
+    buf
+}
+
+// Write out a term, with one product per line.
+fn term_to_string(pin_names: &[String], term: &Term) -> String {
+    if is_true(term) {
+        return String::from("VCC");
+    }
+    if term.pins.is_empty() {
+        return String::from("GND");
+    }
+
+    term.pins
+        .iter()
+        .map(|product| {
+            product
+                .iter()
+                .map(|pin| {
+                    format!(
+                        "{}{}",
+                        if pin.neg { "/" } else { "" },
+                        pin_names[pin.pin - 1]
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" * ")
+        })
+        .collect::<Vec<String>>()
+        .join("\n    + ")
+}
@@ -33,8 +33,6 @@ pub enum ErrorCode {
     ReservedPinName { term: SpecialProductTerm },
     #[error("no suffix is allowed for {term}")]
     SpecialSuffix { term: SpecialProductTerm },
-    #[error("internal error: analyse_mode should never let you use this pin as an input")]
-    BadAnalysis,
     #[error("use of {term} is not allowed in equations")]
     BadSpecial { term: SpecialProductTerm },
     #[error("bad character in input")]
@@ -86,6 +84,8 @@ pub enum ErrorCode {
     NoCLK,
     #[error("'=' expected")]
     NoEquals,
+    #[error("')' expected")]
+    NoCloseParen,
     #[error("pinname expected after '/'")]
     NoPinName,
     #[error(
@@ -96,6 +96,14 @@ pub enum ErrorCode {
     ReservedRegisteredInput { pin: usize, name: &'static str },
     #[error("pin {pin} can't be used as input in complex mode")]
     NotAnComplexModeInput { pin: usize },
+    #[error("pin {pin} can't be used as input in simple mode")]
+    NotASimpleModeInput { pin: usize },
+    #[error("output pin {pin} can't be used as input in simple mode")]
+    SimpleModeFeedback { pin: usize },
+    #[error("registered outputs aren't available in {mode} mode")]
+    RegisteredInMode { mode: &'static str },
+    #[error("tristate outputs aren't available in simple mode")]
+    TristateInSimpleMode,
     #[error("this pin can't be used as output")]
     NotAnOutput,
     #[error("{term} is defined twice")]
@@ -110,12 +118,103 @@ pub enum ErrorCode {
     UndefinedOutput { suffix: OutputSuffix },
     #[error("too many product terms in sum for pin (max: {max}, saw: {seen})")]
     TooManyProducts { max: usize, seen: usize },
+    #[error(
+        "expression expands to more than {max} product terms before minimisation (saw: {seen})"
+    )]
+    ExpansionTooBig { max: usize, seen: usize },
     #[error("GAL16V8/20V8: tri. control for reg. output is not allowed")]
     TristateReg,
     #[error("unknown pinname")]
     UnknownPin,
     #[error("tristate control without previous '.T'")]
     UnmatchedTristate,
+    #[error("bad bus, expected something like 'A[3..0]' or 'A[3:0]'")]
+    BadBus,
+    #[error("bad number")]
+    BadNumber,
+    #[error("a bus can only be compared with a constant or range, as in 'A[3..0] == 5'")]
+    BareBus,
+    #[error("bad range, expected something like ':[C000..DFFF]'")]
+    BadRange,
+    #[error("range doesn't line up with bits {max} to {min} of the bus")]
+    RangeMisaligned { max: usize, min: usize },
+    #[error("{value} doesn't fit in a {width}-bit bus")]
+    BusValueTooBig { value: u64, width: usize },
+    #[error("a bus can't be wider than {max} bits")]
+    BusTooWide { max: usize },
+    #[error("bus bits must be numbered from 0 to {max}")]
+    BusBitTooHigh { max: usize },
+    #[error("LET needs a plain name, as in 'LET X = A * B'")]
+    BadMacro,
+    #[error("{name} is already defined")]
+    RepeatedMacro { name: String },
+    #[error("bad value '{value}' in test vector (expected one of 0, 1, C, H, L, Z, X, N)")]
+    BadVectorValue { value: char },
+    #[error("wrong number of values in test vector (expected: {expected}, saw: {seen})")]
+    BadVectorLength { expected: usize, seen: usize },
+    #[error("test vector failed on pin {pin} (expected: {expected}, saw: {seen})")]
+    VectorFailed {
+        pin: usize,
+        expected: char,
+        seen: char,
+    },
+    #[error("no suitable pin left for {name}")]
+    NoFreePin { name: String },
+    #[error("{name} is not supported on this type of GAL")]
+    UnsupportedDirective { name: String },
+    #[error("unknown directive '%{name}'")]
+    UnknownDirective { name: String },
+    #[error("SIG can't be used with UES OFF")]
+    SigWithoutUES,
+    #[error("bad argument for {name}")]
+    BadDirectiveArg { name: String },
+    #[error("SIG needs up to 16 hex digits after '0x', up to 8 characters in quotes, or CRC")]
+    BadSignature,
+    #[error("a {pal} can only be replaced by a {chip}")]
+    PALWrongChip {
+        pal: &'static str,
+        chip: &'static str,
+    },
+    #[error("MODE doesn't match the mode a {pal} needs")]
+    PALModeConflict { pal: &'static str },
+    #[error("output must be registered to match a {pal}")]
+    PALRegistered { pal: &'static str },
+    #[error("output can't be registered on a {pal}")]
+    PALCombinatorial { pal: &'static str },
+    #[error("output must be active low to match a {pal}")]
+    PALActiveHigh { pal: &'static str },
+    #[error("pin {pin} is a registered output on a {pal}, and can't be used as an input")]
+    PALNotInput { pin: usize, pal: &'static str },
+    // JEDEC reader errors.
+    #[error("start of JEDEC data (STX) expected")]
+    NoSTX,
+    #[error("end of JEDEC data (ETX) expected")]
+    NoETX,
+    #[error("bad JEDEC field")]
+    BadJedecField,
+    #[error("fuse count (*QF) expected before fuse data")]
+    NoFuseCount,
+    #[error("no supported GAL has {count} fuses")]
+    BadFuseCount { count: usize },
+    #[error("fuse data runs past the end of the fuse map")]
+    FuseOutOfRange,
+    #[error("fuses left unset, with no default (*F) given")]
+    UnsetFuses,
+    #[error("fuse checksum mismatch (calculated: {calculated:04x}, file: {file:04x})")]
+    BadFuseChecksum { calculated: u16, file: u16 },
+    #[error("file checksum mismatch (calculated: {calculated:04x}, file: {file:04x})")]
+    BadFileChecksum { calculated: u16, file: u16 },
+    #[error("GAL16V8/20V8: invalid mode (SYN and AC0 both clear)")]
+    BadMode,
+    #[error("a {pal} has {expected} fuses, but the file has {count}")]
+    PALFuseCount {
+        pal: &'static str,
+        expected: usize,
+        count: usize,
+    },
+    // Simulator errors.
+    #[error("outputs don't settle (combinatorial loop?)")]
+    Unstable,
 }
 
 // Adapt an ErrorCode to an Error.
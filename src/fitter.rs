@@ -0,0 +1,165 @@
+//
+// fitter.rs: Fitting equations into the available resources
+//
+// The passes here rewrite a Blueprint so that it has a better chance
+// of fitting onto the chip, for designs that would otherwise fail
+// with too many product terms. They are all opt-in, since they change
+// the fuse map away from what was literally asked for.
+//
+
+use crate::{
+    blueprint::{self, Active, Blueprint, PinMode},
+    chips::Chip,
+    gal::{self, Mode, Pin, Term},
+    gal_builder, minimiser,
+};
+
+// Where an output's equation doesn't fit in the OLMC's rows, try
+// implementing the complement of the equation with the opposite
+// output polarity instead. The equation is only minimised first if
+// 'minimise' is set.
+pub fn choose_polarity(blueprint: &mut Blueprint, minimise: bool) {
+    // A PAL's output polarities are fixed.
+    if blueprint.directives.pal.is_some() {
+        return;
+    }
+
+    // On the GAL22V10 family and GAL20RA10, the polarity is applied
+    // after the flip-flop, so inverting a registered output would
+    // change the level that the resets and presets put on the pin.
+    let polarity_after_reg = blueprint.chip.has_arsp() || blueprint.chip == Chip::GAL20RA10;
+
+    for i in 0..blueprint.olmcs.len() {
+        let rows = gal_builder::main_term_rows(blueprint, i);
+        let chip = blueprint.chip;
+        let olmc = &mut blueprint.olmcs[i];
+
+        let term = match olmc.output {
+            // Registered feedback reads the flip-flop, which would
+            // see the complemented value, so leave those alone.
+            Some((PinMode::Registered, _)) if olmc.feedback || polarity_after_reg => continue,
+            Some((_, ref mut term)) => term,
+            None => continue,
+        };
+
+        if term.pins.len() <= rows || blueprint::uses_power(chip, term) {
+            continue;
+        }
+
+        if minimise {
+            let minimised = minimiser::minimise(term);
+            if minimised.pins.len() <= rows {
+                *term = minimised;
+                continue;
+            }
+        }
+
+        let complement = minimiser::complement(term);
+        if complement.pins.len() <= rows {
+            *term = complement;
+            olmc.active = match olmc.active {
+                Active::Low => Active::High,
+                Active::High => Active::Low,
+            };
+            olmc.inverted = true;
+        }
+    }
+}
+
+// Where an output's equation still doesn't fit, move some of its
+// products onto a spare OLMC, and OR that OLMC's output back in
+// through its feedback path. Only OLMCs on pins named "NC" are used,
+// so that nothing gets driven that might be wired up.
+pub fn split_products(blueprint: &mut Blueprint) {
+    // A PAL's outputs are fixed, and simple mode has no feedback.
+    if blueprint.directives.pal.is_some() || blueprint.directives.mode == Some(Mode::Simple) {
+        return;
+    }
+
+    // Using a spare can move a GAL16V8 or GAL20V8 out of simple mode,
+    // taking a row from every output, so keep going until nothing
+    // changes.
+    while (0..blueprint.olmcs.len()).any(|i| split_olmc(blueprint, i)) {}
+}
+
+// Try to split an OLMC's equation, returning whether anything changed.
+fn split_olmc(blueprint: &mut Blueprint, olmc_num: usize) -> bool {
+    let chip = blueprint.chip;
+    let rows = gal_builder::main_term_rows(blueprint, olmc_num);
+    let (line_num, products) = match blueprint.olmcs[olmc_num].output {
+        Some((_, ref term)) if term.pins.len() > rows && !blueprint::uses_power(chip, term) => {
+            (term.line_num, term.pins.clone())
+        }
+        _ => return false,
+    };
+
+    // Try the spares with the most rows first.
+    let mut spares = (0..blueprint.olmcs.len())
+        .filter(|j| is_spare(blueprint, *j))
+        .collect::<Vec<_>>();
+    spares.sort_by_key(|j| std::cmp::Reverse(chip.num_rows_for_olmc(*j)));
+
+    for spare in spares {
+        let mut candidate = blueprint.clone();
+        let spare_olmc = &mut candidate.olmcs[spare];
+        spare_olmc.output = Some((PinMode::Combinatorial, gal::false_term(line_num)));
+        spare_olmc.active = Active::High;
+        spare_olmc.feedback = true;
+
+        if !gal_builder::has_feedback(&candidate, spare) {
+            continue;
+        }
+
+        // The spare takes as much as it can, and the main term keeps
+        // a row for the spare's output.
+        let main_rows = gal_builder::main_term_rows(&candidate, olmc_num);
+        let spare_rows = gal_builder::main_term_rows(&candidate, spare);
+        if main_rows < 2 || spare_rows == 0 {
+            continue;
+        }
+        let mut kept = products.clone();
+        let moved = kept.split_off(usize::max(
+            main_rows - 1,
+            kept.len().saturating_sub(spare_rows),
+        ));
+        let spare_pin = chip.olmc_to_pin(spare);
+        kept.push(vec![Pin {
+            pin: spare_pin,
+            neg: false,
+        }]);
+
+        if let Some((_, ref mut term)) = candidate.olmcs[olmc_num].output {
+            term.pins = kept;
+        }
+        candidate.olmcs[spare].output = Some((
+            PinMode::Combinatorial,
+            Term {
+                line_num,
+                pins: moved,
+            },
+        ));
+        candidate.pins[spare_pin - 1] = spare_name(&candidate, olmc_num);
+
+        *blueprint = candidate;
+        return true;
+    }
+
+    false
+}
+
+// Can the OLMC take part of another output's equation?
+fn is_spare(blueprint: &Blueprint, olmc_num: usize) -> bool {
+    let olmc = &blueprint.olmcs[olmc_num];
+    let pin = blueprint.chip.olmc_to_pin(olmc_num);
+    olmc.output.is_none() && !olmc.feedback && blueprint.pins[pin - 1] == "NC"
+}
+
+// Name a spare after the output it's helping, e.g. "O1_PART2".
+fn spare_name(blueprint: &Blueprint, olmc_num: usize) -> String {
+    let chip = blueprint.chip;
+    let name = blueprint.pins[chip.olmc_to_pin(olmc_num) - 1].trim_start_matches('/');
+    (1..)
+        .map(|n| format!("{}_PART{}", name, n))
+        .find(|part| !blueprint.pins.contains(part))
+        .unwrap()
+}
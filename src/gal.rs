@@ -64,7 +64,6 @@ pub enum Mode {
 // depend on the mode settings for the GALxxV8s, so they're here rather
 // than in chips.rs.
 
-const BAD: Result<i32, ErrorCode> = Err(ErrorCode::BadAnalysis);
 const PWR: Result<i32, ErrorCode> = Err(ErrorCode::BadPower);
 
 const REG_P1: Result<i32, ErrorCode> = Err(ErrorCode::ReservedRegisteredInput {
@@ -80,6 +79,11 @@ const REG_P13: Result<i32, ErrorCode> = Err(ErrorCode::ReservedRegisteredInput {
     name: "/OE",
 });
 
+const SMPL_P15: Result<i32, ErrorCode> = Err(ErrorCode::NotASimpleModeInput { pin: 15 });
+const SMPL_P16: Result<i32, ErrorCode> = Err(ErrorCode::NotASimpleModeInput { pin: 16 });
+const SMPL_P18: Result<i32, ErrorCode> = Err(ErrorCode::NotASimpleModeInput { pin: 18 });
+const SMPL_P19: Result<i32, ErrorCode> = Err(ErrorCode::NotASimpleModeInput { pin: 19 });
+
 const CPLX_P12: Result<i32, ErrorCode> = Err(ErrorCode::NotAnComplexModeInput { pin: 12 });
 const CPLX_P15: Result<i32, ErrorCode> = Err(ErrorCode::NotAnComplexModeInput { pin: 15 });
 const CPLX_P19: Result<i32, ErrorCode> = Err(ErrorCode::NotAnComplexModeInput { pin: 19 });
@@ -98,7 +102,7 @@ const P13_20RA10: Result<i32, ErrorCode> = Err(ErrorCode::ReservedInputGAL20RA10
 #[rustfmt::skip]
 const PIN_TO_COL_16_SIMPLE: [Result<i32, ErrorCode>; 20] = [
     Ok(2),  Ok(0),  Ok(4),  Ok(8),  Ok(12), Ok(16), Ok(20), Ok(24), Ok(28), PWR,
-    Ok(30), Ok(26), Ok(22), Ok(18), BAD,    BAD,    Ok(14), Ok(10), Ok(6),  PWR,
+    Ok(30), Ok(26), Ok(22), Ok(18), SMPL_P15, SMPL_P16, Ok(14), Ok(10), Ok(6),  PWR,
 ];
 #[rustfmt::skip]
 const PIN_TO_COL_16_COMPLEX: [Result<i32, ErrorCode>; 20] = [
@@ -115,7 +119,7 @@ const PIN_TO_COL_16_REGISTERED: [Result<i32, ErrorCode>; 20] = [
 #[rustfmt::skip]
 const PIN_TO_COL_20_SIMPLE: [Result<i32, ErrorCode>; 24] = [
     Ok(2),  Ok(0),  Ok(4),  Ok(8),  Ok(12), Ok(16), Ok(20), Ok(24), Ok(28), Ok(32), Ok(36), PWR,
-    Ok(38), Ok(34), Ok(30), Ok(26), Ok(22), BAD,    BAD,    Ok(18), Ok(14), Ok(10), Ok(6),  PWR,
+    Ok(38), Ok(34), Ok(30), Ok(26), Ok(22), SMPL_P18, SMPL_P19, Ok(18), Ok(14), Ok(10), Ok(6),  PWR,
 ];
 #[rustfmt::skip]
 const PIN_TO_COL_20_COMPLEX: [Result<i32, ErrorCode>; 24] = [
@@ -135,6 +139,20 @@ const PIN_TO_COL_22V10: [Result<i32, ErrorCode>; 24] = [
     Ok(42), Ok(38), Ok(34), Ok(30), Ok(26), Ok(22), Ok(18), Ok(14), Ok(10), Ok(6),  Ok(2),  PWR,
 ];
 
+// GAL18V10
+#[rustfmt::skip]
+const PIN_TO_COL_18V10: [Result<i32, ErrorCode>; 20] = [
+    Ok(0),  Ok(4),  Ok(8),  Ok(12), Ok(16), Ok(20), Ok(24), Ok(28), Ok(34), PWR,
+    Ok(32), Ok(30), Ok(26), Ok(22), Ok(18), Ok(14), Ok(10), Ok(6),  Ok(2),  PWR,
+];
+
+// GAL26CV12
+#[rustfmt::skip]
+const PIN_TO_COL_26CV12: [Result<i32, ErrorCode>; 28] = [
+    Ok(0),  Ok(4),  Ok(8),  Ok(12), Ok(16), Ok(20), PWR,    Ok(24), Ok(28), Ok(32), Ok(36), Ok(40), Ok(44), Ok(48),
+    Ok(46), Ok(42), Ok(38), Ok(34), Ok(30), Ok(26), PWR,    Ok(22), Ok(18), Ok(14), Ok(10), Ok(6),  Ok(2),  Ok(50),
+];
+
 // GAL20RA10
 #[rustfmt::skip]
 const PIN_TO_COL_20RA10: [Result<i32, ErrorCode>; 24] = [
@@ -161,9 +179,15 @@ impl GAL {
         }
     }
 
+    // The size of the bitstream, which is shorter if the UES
+    // has been left out (see 'Chip::has_optional_ues').
+    pub fn total_size(&self) -> usize {
+        self.chip.total_size() - 64 + self.sig.len()
+    }
+
     // Set the fuses associated with mode for GALxxV8s.
     pub fn set_mode(&mut self, mode: Mode) {
-        assert!(self.chip == Chip::GAL16V8 || self.chip == Chip::GAL20V8);
+        assert!(self.chip.arch() == Chip::GAL16V8 || self.chip.arch() == Chip::GAL20V8);
         match mode {
             Mode::Simple => {
                 self.syn = true;
@@ -182,7 +206,7 @@ impl GAL {
 
     // Retrive the mode from the mode fuses.
     pub fn get_mode(&self) -> Mode {
-        assert!(self.chip == Chip::GAL16V8 || self.chip == Chip::GAL20V8);
+        assert!(self.chip.arch() == Chip::GAL16V8 || self.chip.arch() == Chip::GAL20V8);
         match (self.syn, self.ac0) {
             (true, false) => Mode::Simple,
             (true, true) => Mode::Complex,
@@ -191,9 +215,10 @@ impl GAL {
         }
     }
 
-    // Horrible special-case test for registered outputs on the GAL22V10.
-    fn needs_flip(&self, pin_num: usize) -> bool {
-        if self.chip != Chip::GAL22V10 {
+    // Horrible special-case test for registered outputs on the
+    // GAL22V10 (and GAL18V10 and GAL26CV12).
+    pub(crate) fn needs_flip(&self, pin_num: usize) -> bool {
+        if !self.chip.has_arsp() {
             return false;
         }
 
@@ -207,17 +232,17 @@ impl GAL {
     // Enter a term into the given set of rows of the main logic array.
     pub fn add_term(&mut self, term: &Term, bounds: &Bounds) -> Result<(), Error> {
         let mut bounds = *bounds;
-        let single_row = bounds.max_row == bounds.row_offset + 1;
+        let available = bounds.max_row - bounds.row_offset;
         for row in term.pins.iter() {
             if bounds.row_offset == bounds.max_row {
                 // too many ORs?
                 return at_line(
                     term.line_num,
-                    Err(if single_row {
+                    Err(if available == 1 {
                         ErrorCode::MoreThanOneProduct
                     } else {
                         ErrorCode::TooManyProducts {
-                            max: bounds.max_row - 1,
+                            max: available,
                             seen: term.pins.len(),
                         }
                     }),
@@ -265,28 +290,43 @@ impl GAL {
         }
     }
 
-    // Map the input pin number to the fuse column number.
-    fn pin_to_column(&self, pin_num: usize) -> Result<usize, ErrorCode> {
-        let column_lookup: &[Result<i32, ErrorCode>] = match self.chip {
-            Chip::GAL16V8 => match self.get_mode() {
+    // The pin-to-column mapping in use, given the chip and mode.
+    fn column_lookup(&self) -> &'static [Result<i32, ErrorCode>] {
+        match self.chip {
+            Chip::GAL16V8 | Chip::ATF16V8B => match self.get_mode() {
                 Mode::Simple => &PIN_TO_COL_16_SIMPLE,
                 Mode::Complex => &PIN_TO_COL_16_COMPLEX,
                 Mode::Registered => &PIN_TO_COL_16_REGISTERED,
             },
-            Chip::GAL20V8 => match self.get_mode() {
+            Chip::GAL20V8 | Chip::ATF20V8B => match self.get_mode() {
                 Mode::Simple => &PIN_TO_COL_20_SIMPLE,
                 Mode::Complex => &PIN_TO_COL_20_COMPLEX,
                 Mode::Registered => &PIN_TO_COL_20_REGISTERED,
             },
-            Chip::GAL22V10 => &PIN_TO_COL_22V10,
+            Chip::GAL22V10 | Chip::ATF22V10C => &PIN_TO_COL_22V10,
+            Chip::GAL18V10 => &PIN_TO_COL_18V10,
+            Chip::GAL26CV12 => &PIN_TO_COL_26CV12,
             Chip::GAL20RA10 => &PIN_TO_COL_20RA10,
-        };
+        }
+    }
 
-        let column = column_lookup[pin_num - 1].clone()?;
+    // Map the input pin number to the fuse column number.
+    fn pin_to_column(&self, pin_num: usize) -> Result<usize, ErrorCode> {
+        let column = self.column_lookup()[pin_num - 1].clone()?;
 
         Ok(column as usize)
     }
 
+    // And the inverse, mapping a fuse column (either the true or
+    // negated column) back to the input pin number.
+    pub fn column_to_pin(&self, column: usize) -> Option<usize> {
+        let column = (column & !1) as i32;
+        self.column_lookup()
+            .iter()
+            .position(|col| matches!(col, Ok(col) if *col == column))
+            .map(|idx| idx + 1)
+    }
+
     // Add an 'AND' term to a fuse map.
     fn set_and(&mut self, row: usize, pin_num: usize, negation: bool) -> Result<(), ErrorCode> {
         let chip = self.chip;
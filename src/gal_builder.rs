@@ -10,14 +10,22 @@ use crate::{
     chips::Chip,
     errors::{at_line, Error, ErrorCode, OutputSuffix},
     gal::{self, Bounds, Mode, GAL},
+    pal::PAL,
 };
 
 pub fn build(blueprint: &Blueprint) -> Result<GAL, Error> {
     let mut gal = GAL::new(blueprint.chip);
+    if !blueprint.directives.ues {
+        gal.sig.clear();
+    }
 
     match gal.chip {
-        Chip::GAL16V8 | Chip::GAL20V8 => build_galxv8(&mut gal, blueprint)?,
-        Chip::GAL22V10 => build_gal22v10(&mut gal, blueprint)?,
+        Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => {
+            build_galxv8(&mut gal, blueprint)?
+        }
+        Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+            build_gal22v10(&mut gal, blueprint)?
+        }
         Chip::GAL20RA10 => build_gal20ra10(&mut gal, blueprint)?,
     }
 
@@ -32,6 +40,10 @@ fn build_galxv8(gal: &mut GAL, blueprint: &Blueprint) -> Result<(), Error> {
     check_not_gal20ra10(blueprint)?;
     set_sig(gal, blueprint);
     set_mode(gal, blueprint);
+    check_mode(gal.get_mode(), blueprint)?;
+    if let Some(pal) = blueprint.directives.pal {
+        check_pal(pal, blueprint)?;
+    }
     // Are we implementing combinatorial expressions as tristate?
     // Pure combinatorial is only available in simple mode.
     let com_is_tri = gal.get_mode() != Mode::Simple;
@@ -70,8 +82,8 @@ fn build_gal20ra10(gal: &mut GAL, blueprint: &Blueprint) -> Result<(), Error> {
 
 // Write out the signature.
 fn set_sig(gal: &mut GAL, blueprint: &Blueprint) {
-    // Signature has space for 8 bytes.
-    for i in 0..usize::min(blueprint.sig.len(), 8) {
+    // Signature has space for 8 bytes, or none on chips without a UES.
+    for i in 0..usize::min(blueprint.sig.len(), gal.sig.len() / 8) {
         let c = blueprint.sig[i];
         for j in 0..8 {
             gal.sig[i * 8 + j] = (c << j) & 0x80 != 0;
@@ -129,11 +141,11 @@ fn set_core_eqns(gal: &mut GAL, blueprint: &Blueprint) -> Result<(), Error> {
     Ok(())
 }
 
-// Set the AR and SP equations, unique to the GAL22V10.
+// Set the AR and SP equations, unique to the GAL22V10, GAL18V10 and GAL26CV12.
 fn set_arsp_eqns(gal: &mut GAL, blueprint: &Blueprint) -> Result<(), Error> {
     // AR
     let ar_bounds = Bounds {
-        start_row: 0,
+        start_row: gal.chip.ar_row(),
         max_row: 1,
         row_offset: 0,
     };
@@ -141,7 +153,7 @@ fn set_arsp_eqns(gal: &mut GAL, blueprint: &Blueprint) -> Result<(), Error> {
 
     // SP
     let sp_bounds = Bounds {
-        start_row: 131,
+        start_row: gal.chip.sp_row(),
         max_row: 1,
         row_offset: 0,
     };
@@ -203,22 +215,68 @@ fn set_xors(gal: &mut GAL, blueprint: &Blueprint) {
     }
 }
 
-// We don't do anything with the PT bits in the GALxxV8s.
+// By default, we don't do anything with the PT bits in the GALxxV8s.
 fn set_pts(gal: &mut GAL) {
     for bit in gal.pt.iter_mut() {
         *bit = true;
     }
 }
 
+// Optionally, the PT bits can be used to disable the product terms
+// for rows that have been cleared. This doesn't change the logic, as
+// a cleared row is always false anyway.
+pub fn disable_unused_pts(gal: &mut GAL) {
+    if gal.chip.arch() != Chip::GAL16V8 && gal.chip.arch() != Chip::GAL20V8 {
+        return;
+    }
+
+    let row_len = gal.chip.num_cols();
+    for (pt, row) in gal.pt.iter_mut().zip(gal.fuses.chunks(row_len)) {
+        if row.iter().all(|fuse| !fuse) {
+            *pt = false;
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////
 // Other helper functions.
 //
 
+// Number of rows available for the main term of the given OLMC, once
+// tristate enables etc. have been accounted for.
+pub fn main_term_rows(blueprint: &Blueprint, olmc_num: usize) -> usize {
+    let mut gal = GAL::new(blueprint.chip);
+    if let Chip::GAL16V8 | Chip::GAL20V8 = gal.chip.arch() {
+        set_mode(&mut gal, blueprint);
+    }
+    let bounds = adjust_main_bounds(
+        &gal,
+        &blueprint.olmcs[olmc_num].output,
+        &gal.chip.get_bounds(olmc_num),
+    );
+    bounds.max_row - bounds.row_offset
+}
+
+// Can the OLMC's output be fed back into the array? Only some of them
+// can in complex mode, and none in simple mode.
+pub fn has_feedback(blueprint: &Blueprint, olmc_num: usize) -> bool {
+    let mut gal = GAL::new(blueprint.chip);
+    if let Chip::GAL16V8 | Chip::GAL20V8 = gal.chip.arch() {
+        set_mode(&mut gal, blueprint);
+        match gal.get_mode() {
+            Mode::Simple => return false,
+            Mode::Complex => return olmc_num != 0 && olmc_num != gal.chip.num_olmcs() - 1,
+            Mode::Registered => {}
+        }
+    }
+    true
+}
+
 // Adjust the bounds for the main term of there's a tristate enable
 // term etc. in the first rows.
 fn adjust_main_bounds(gal: &GAL, output: &Option<(PinMode, gal::Term)>, bounds: &Bounds) -> Bounds {
     match gal.chip {
-        Chip::GAL16V8 | Chip::GAL20V8 => {
+        Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => {
             // Registered outputs don't have a tristate enable, or
             // indeed any pins in simple mode.
             let reg_out = matches!(output, Some((PinMode::Registered, _)));
@@ -233,7 +291,7 @@ fn adjust_main_bounds(gal: &GAL, output: &Option<(PinMode, gal::Term)>, bounds:
             }
         }
         // Skip tristate enable.
-        Chip::GAL22V10 => Bounds {
+        Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => Bounds {
             row_offset: 1,
             ..*bounds
         },
@@ -282,7 +340,9 @@ fn check_tristate(chip: Chip, olmc: &OLMC) -> Result<(), ErrorCode> {
         None => Err(ErrorCode::UndefinedOutput {
             suffix: OutputSuffix::E,
         }),
-        Some((PinMode::Registered, _)) if chip == Chip::GAL16V8 || chip == Chip::GAL20V8 => {
+        Some((PinMode::Registered, _))
+            if chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8 =>
+        {
             Err(ErrorCode::TristateReg)
         }
         Some((PinMode::Combinatorial, _)) => Err(ErrorCode::UnmatchedTristate),
@@ -290,6 +350,87 @@ fn check_tristate(chip: Chip, olmc: &OLMC) -> Result<(), ErrorCode> {
     }
 }
 
+// Check that the outputs can be implemented in the given mode. The
+// mode picked by 'analyse_mode' always passes, but one set by a
+// directive may not.
+fn check_mode(mode: Mode, blueprint: &Blueprint) -> Result<(), Error> {
+    let mode_name = match mode {
+        Mode::Simple => "simple",
+        Mode::Complex => "complex",
+        Mode::Registered => return Ok(()),
+    };
+    for (olmc, i) in blueprint.olmcs.iter().zip(0..) {
+        if let Some((pin_mode, term)) = &olmc.output {
+            let res = match pin_mode {
+                PinMode::Registered => Err(ErrorCode::RegisteredInMode { mode: mode_name }),
+                PinMode::Tristate if mode == Mode::Simple => Err(ErrorCode::TristateInSimpleMode),
+                _ => Ok(()),
+            };
+            at_line(term.line_num, res)?;
+
+            // Simple mode outputs have no feedback path.
+            if mode == Mode::Simple && olmc.feedback {
+                let pin = blueprint.chip.olmc_to_pin(i);
+                if let Some(term) = find_use(blueprint, pin) {
+                    return at_line(term.line_num, Err(ErrorCode::SimpleModeFeedback { pin }));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Check that the outputs match those of the PAL being replaced. The
+// mode has already been set to match the PAL.
+fn check_pal(pal: PAL, blueprint: &Blueprint) -> Result<(), Error> {
+    let pal_name = pal.name();
+    for (olmc, i) in blueprint.olmcs.iter().zip(0..) {
+        let registered = pal.is_registered(i);
+        match &olmc.output {
+            Some((pin_mode, term)) => {
+                let res = if registered && *pin_mode != PinMode::Registered {
+                    Err(ErrorCode::PALRegistered { pal: pal_name })
+                } else if !registered && *pin_mode == PinMode::Registered {
+                    Err(ErrorCode::PALCombinatorial { pal: pal_name })
+                } else if olmc.active == Active::High {
+                    Err(ErrorCode::PALActiveHigh { pal: pal_name })
+                } else {
+                    Ok(())
+                };
+                at_line(term.line_num, res)?;
+            }
+            // A GAL would turn an undriven registered OLMC into an
+            // input, which the PAL can't do.
+            None if registered && olmc.feedback => {
+                let pin = blueprint.chip.olmc_to_pin(i);
+                if let Some(term) = find_use(blueprint, pin) {
+                    return at_line(
+                        term.line_num,
+                        Err(ErrorCode::PALNotInput { pin, pal: pal_name }),
+                    );
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+// Find a term in the blueprint that uses the pin as an input, for
+// reporting errors against.
+fn find_use(blueprint: &Blueprint, pin: usize) -> Option<&gal::Term> {
+    blueprint
+        .olmcs
+        .iter()
+        .flat_map(|olmc| {
+            olmc.output
+                .iter()
+                .map(|(_, term)| term)
+                .chain(olmc.tri_con.iter())
+        })
+        .find(|term| term.pins.iter().flatten().any(|input| input.pin == pin))
+}
+
 fn check_aux(field: &Option<gal::Term>, olmc: &OLMC, suffix: OutputSuffix) -> Result<(), Error> {
     if let Some(ref term) = field {
         at_line(
@@ -309,7 +450,13 @@ fn check_aux(field: &Option<gal::Term>, olmc: &OLMC, suffix: OutputSuffix) -> Re
 // GALxV8 analysis - determine which mode to run the chip in.
 
 fn set_mode(gal: &mut GAL, blueprint: &Blueprint) {
-    gal.set_mode(analyse_mode(&blueprint.olmcs));
+    let directives = &blueprint.directives;
+    gal.set_mode(match (directives.pal, directives.mode) {
+        // A replacement for a PAL must use the PAL's pinout.
+        (Some(pal), _) => pal.mode(),
+        (None, Some(mode)) => mode,
+        (None, None) => analyse_mode(&blueprint.olmcs),
+    });
 }
 
 fn analyse_mode(olmcs: &[OLMC]) -> Mode {
@@ -374,6 +521,7 @@ mod tests {
             arst: None,
             aprst: None,
             feedback: false,
+            inverted: false,
         }
     }
 
@@ -386,6 +534,7 @@ mod tests {
             arst: None,
             aprst: None,
             feedback: true,
+            inverted: false,
         }
     }
 
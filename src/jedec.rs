@@ -0,0 +1,319 @@
+//
+// jedec.rs: JEDEC file reader
+//
+// This module reads a JEDEC fuse file back into a GAL structure, so
+// that files produced by other tools (or found on old boards) can be
+// inspected and checked. It's the inverse of 'make_jedec' in
+// writer.rs.
+//
+
+use std::fs;
+
+use crate::{
+    chips::Chip,
+    errors::{Error, ErrorCode, LineNum},
+    gal::GAL,
+    pal::PAL,
+    writer::{self, CheckSummer},
+};
+
+// The result of reading a JEDEC file: the fuse state, plus the
+// security bit, which isn't part of the GAL structure.
+pub struct Jedec {
+    pub gal: GAL,
+    pub sec_bit: bool,
+}
+
+const STX: char = '\x02';
+const ETX: char = '\x03';
+
+// The fuse data read from a JEDEC file, before it's interpreted as
+// any particular device.
+struct Contents<'a> {
+    header: &'a str,
+    bits: Vec<bool>,
+    sec_bit: bool,
+    // Where errors about the file as a whole are reported.
+    etx_line: LineNum,
+}
+
+// Fuse state as it's built up from the fields of the file.
+struct Fuses {
+    size: Option<usize>,
+    default: Option<bool>,
+    bits: Vec<Option<bool>>,
+    sec_bit: bool,
+    checksum: Option<(LineNum, u16)>,
+}
+
+pub fn parse(file_name: &str) -> Result<Jedec, Error> {
+    let data = fs::read_to_string(file_name).expect("Unable to read file");
+    parse_str(&data)
+}
+
+pub fn parse_str(data: &str) -> Result<Jedec, Error> {
+    let contents = read_fuses(data)?;
+    let at_etx = |code| Error {
+        code,
+        line: contents.etx_line,
+    };
+
+    let size = contents.bits.len();
+    let named = contents
+        .header
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Device:"))
+        .filter_map(|name| Chip::from_name(name.trim()).ok())
+        .collect::<Vec<Chip>>();
+    // The size only gives us the fuse layout. If the header names a
+    // compatible part, as galette's does, use that. A map without the
+    // UES is only taken for a part that the header names.
+    let chip = match Chip::from_total_size(size) {
+        Some(chip) => named
+            .into_iter()
+            .find(|named| named.arch() == chip.arch() && named.total_size() == chip.total_size())
+            .unwrap_or(chip),
+        None => named
+            .into_iter()
+            .find(|named| named.has_optional_ues() && named.total_size() - 64 == size)
+            .ok_or_else(|| at_etx(ErrorCode::BadFuseCount { count: size }))?,
+    };
+
+    let gal = make_gal(chip, &contents.bits);
+
+    // Only three of the four GALxxV8 mode settings are valid.
+    if (chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8) && !gal.syn && !gal.ac0 {
+        return Err(at_etx(ErrorCode::BadMode));
+    }
+
+    Ok(Jedec {
+        gal,
+        sec_bit: contents.sec_bit,
+    })
+}
+
+// Read the JEDEC file for a PAL, and convert it into the GAL that
+// replaces the PAL.
+pub fn parse_pal(file_name: &str, pal: PAL) -> Result<Jedec, Error> {
+    let data = fs::read_to_string(file_name).expect("Unable to read file");
+    parse_pal_str(&data, pal)
+}
+
+pub fn parse_pal_str(data: &str, pal: PAL) -> Result<Jedec, Error> {
+    let contents = read_fuses(data)?;
+
+    // A PAL's fuses are just its AND array.
+    let expected = pal.chip().logic_size();
+    let count = contents.bits.len();
+    if count != expected {
+        return Err(Error {
+            code: ErrorCode::PALFuseCount {
+                pal: pal.name(),
+                expected,
+                count,
+            },
+            line: contents.etx_line,
+        });
+    }
+
+    Ok(Jedec {
+        gal: pal.make_gal(&contents.bits),
+        sec_bit: contents.sec_bit,
+    })
+}
+
+// Read the fields of a JEDEC file, checking the checksums.
+fn read_fuses(data: &str) -> Result<Contents<'_>, Error> {
+    // Line numbers are only used for error reporting.
+    let line_at = |offset: usize| data[..offset].matches('\n').count() + 1;
+
+    let stx = data.find(STX).ok_or(Error {
+        code: ErrorCode::NoSTX,
+        line: 1,
+    })?;
+    let etx = match data[stx..].find(ETX) {
+        Some(offset) => stx + offset,
+        None => {
+            return Err(Error {
+                code: ErrorCode::NoETX,
+                line: line_at(data.len()),
+            })
+        }
+    };
+    let etx_line = line_at(etx);
+
+    let mut fuses = Fuses {
+        size: None,
+        default: None,
+        bits: Vec::new(),
+        sec_bit: false,
+        checksum: None,
+    };
+
+    // Fields are terminated by '*'. Everything up to the first one is
+    // a free-form header, which we skip.
+    let mut offset = stx + 1;
+    for (i, field) in data[stx + 1..etx].split('*').enumerate() {
+        let start = offset + (field.len() - field.trim_start().len());
+        offset += field.len() + 1;
+        if i == 0 {
+            continue;
+        }
+        let line = line_at(start);
+        parse_field(&mut fuses, line, field.trim()).map_err(|code| Error { code, line })?;
+    }
+
+    let at_etx = |code| Error {
+        code,
+        line: etx_line,
+    };
+
+    // Fill in the defaults, and check everything's set.
+    if fuses.size.is_none() {
+        return Err(at_etx(ErrorCode::NoFuseCount));
+    }
+    let bits = fuses
+        .bits
+        .iter()
+        .map(|bit| bit.or(fuses.default))
+        .collect::<Option<Vec<bool>>>()
+        .ok_or_else(|| at_etx(ErrorCode::UnsetFuses))?;
+
+    if let Some((line, file)) = fuses.checksum {
+        let mut check_summer = CheckSummer::new();
+        for bit in bits.iter() {
+            check_summer.add(*bit);
+        }
+        let calculated = check_summer.get();
+        if calculated != file {
+            return Err(Error {
+                code: ErrorCode::BadFuseChecksum { calculated, file },
+                line,
+            });
+        }
+    }
+
+    // The file checksum covers everything from STX to ETX inclusive.
+    // A checksum of 0000 is a dummy value, and not checked.
+    let file_sum = data[etx + 1..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect::<String>();
+    if !file_sum.is_empty() {
+        let file =
+            u16::from_str_radix(&file_sum, 16).map_err(|_| at_etx(ErrorCode::BadJedecField))?;
+        let calculated = writer::file_checksum(&data.as_bytes()[stx..=etx]);
+        if file != 0 && file != calculated {
+            return Err(at_etx(ErrorCode::BadFileChecksum { calculated, file }));
+        }
+    }
+
+    Ok(Contents {
+        header: data[stx + 1..etx].split('*').next().unwrap_or(""),
+        bits,
+        sec_bit: fuses.sec_bit,
+        etx_line,
+    })
+}
+
+// Process a single field, with the trailing '*' removed.
+fn parse_field(fuses: &mut Fuses, line: LineNum, field: &str) -> Result<(), ErrorCode> {
+    let mut chars = field.chars();
+    match chars.next() {
+        Some('Q') => {
+            // Only the fuse count matters to us.
+            if let Some(count) = field.strip_prefix("QF") {
+                let size = parse_decimal(count)?;
+                // Don't trust the file with the allocation size.
+                if size > Chip::max_total_size() {
+                    return Err(ErrorCode::BadFuseCount { count: size });
+                }
+                fuses.size = Some(size);
+                fuses.bits = vec![None; size];
+            }
+        }
+        Some('F') => fuses.default = Some(parse_bit(chars.as_str())?),
+        Some('G') => fuses.sec_bit = parse_bit(chars.as_str())?,
+        Some('C') => {
+            let sum = chars.as_str().trim();
+            let sum = u16::from_str_radix(sum, 16).map_err(|_| ErrorCode::BadJedecField)?;
+            fuses.checksum = Some((line, sum));
+        }
+        Some('L') => {
+            if fuses.size.is_none() {
+                return Err(ErrorCode::NoFuseCount);
+            }
+            let rest = chars.as_str().trim_start();
+            let addr_len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let addr = parse_decimal(&rest[..addr_len])?;
+            let data = rest[addr_len..].chars().filter(|c| !c.is_whitespace());
+            for (addr, c) in (addr..).zip(data) {
+                let bit = match c {
+                    '0' => false,
+                    '1' => true,
+                    _ => return Err(ErrorCode::BadJedecField),
+                };
+                *fuses.bits.get_mut(addr).ok_or(ErrorCode::FuseOutOfRange)? = Some(bit);
+            }
+        }
+        // Notes, test vectors, etc. aren't needed to reconstruct the
+        // fuses.
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_decimal(s: &str) -> Result<usize, ErrorCode> {
+    s.trim().parse().map_err(|_| ErrorCode::BadJedecField)
+}
+
+fn parse_bit(s: &str) -> Result<bool, ErrorCode> {
+    match s.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(ErrorCode::BadJedecField),
+    }
+}
+
+// Copy bits from the front of 'src' into 'dest', returning the rest.
+fn take<'a>(dest: &mut [bool], src: &'a [bool]) -> &'a [bool] {
+    let (head, tail) = src.split_at(dest.len());
+    dest.copy_from_slice(head);
+    tail
+}
+
+// Unpack the linear bitstream into the GAL structure, in the order
+// 'make_jedec' writes it out.
+fn make_gal(chip: Chip, bits: &[bool]) -> GAL {
+    let mut gal = GAL::new(chip);
+
+    let mut rest = take(&mut gal.fuses, bits);
+
+    // XOR bits are interleaved with S1 bits on GAL22V10, GAL18V10 and GAL26CV12.
+    if !chip.has_arsp() {
+        rest = take(&mut gal.xor, rest);
+    } else {
+        for i in 0..chip.num_olmcs() {
+            gal.xor[i] = rest[2 * i];
+            gal.ac1[i] = rest[2 * i + 1];
+        }
+        rest = &rest[2 * chip.num_olmcs()..];
+    }
+
+    // A map too short for the UES leaves it out.
+    if bits.len() < chip.total_size() {
+        gal.sig.clear();
+    }
+    rest = take(&mut gal.sig, rest);
+
+    if (chip.arch() == Chip::GAL16V8) || (chip.arch() == Chip::GAL20V8) {
+        rest = take(&mut gal.ac1, rest);
+        rest = take(&mut gal.pt, rest);
+        gal.syn = rest[0];
+        gal.ac0 = rest[1];
+    }
+
+    gal
+}
@@ -15,18 +15,39 @@
 
 pub mod blueprint;
 pub mod chips;
+pub mod disasm;
 pub mod errors;
+pub mod fitter;
 pub mod gal;
 pub mod gal_builder;
+pub mod jedec;
+pub mod minimiser;
+pub mod pal;
 pub mod parser;
+pub mod placer;
+pub mod sim;
 pub mod writer;
 
 pub fn assemble(file_name: &str, config: &writer::Config) -> Result<(), errors::FileError> {
     (|| {
-        let content = parser::parse(file_name)?;
-        let blueprint = blueprint::Blueprint::from(&content)?;
-        let gal = gal_builder::build(&blueprint)?;
-        writer::write_files(file_name, config, &blueprint.pins, &blueprint.olmcs, &gal).unwrap();
+        let mut content = parser::parse(file_name)?;
+        placer::place(&mut content)?;
+        let mut blueprint = blueprint::Blueprint::from(&content)?;
+        if config.minimise {
+            blueprint.minimise();
+        }
+        if config.auto_polarity {
+            fitter::choose_polarity(&mut blueprint, config.minimise);
+        }
+        if config.split {
+            fitter::split_products(&mut blueprint);
+        }
+        let mut gal = gal_builder::build(&blueprint)?;
+        if config.pt_disable {
+            gal_builder::disable_unused_pts(&mut gal);
+        }
+        sim::check_vectors(&gal, &blueprint.vectors)?;
+        writer::write_files(file_name, config, &blueprint, &gal).unwrap();
 
         Ok(())
     })()
@@ -35,3 +56,25 @@ pub fn assemble(file_name: &str, config: &writer::Config) -> Result<(), errors::
         err,
     })
 }
+
+// Read a PAL's JEDEC file and return the JEDEC file for a GAL that
+// replaces it.
+pub fn convert_pal(file_name: &str, pal: pal::PAL) -> Result<String, errors::FileError> {
+    let config = writer::Config::default();
+    jedec::parse_pal(file_name, pal)
+        .map(|jedec| writer::make_jedec(&config, &jedec.gal, &[]))
+        .map_err(|err| errors::FileError {
+            file: file_name.into(),
+            err,
+        })
+}
+
+// Read a JEDEC file and return the equivalent .pld source.
+pub fn disassemble(file_name: &str) -> Result<String, errors::FileError> {
+    jedec::parse(file_name)
+        .map(|jedec| disasm::disassemble(&jedec.gal))
+        .map_err(|err| errors::FileError {
+            file: file_name.into(),
+            err,
+        })
+}
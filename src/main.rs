@@ -9,17 +9,21 @@
 extern crate clap;
 extern crate galette;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 
 use std::process;
 
-use galette::writer;
+use galette::{pal::PAL, writer};
 
 fn main() {
     let matches = App::new("Galette")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Simon Frankau <sgf@arbitrary.name>")
         .about("GALasm-compatible GAL assembler")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        // Otherwise an input file named like "pal2gal" is taken for a
+        // mistyped subcommand.
+        .setting(AppSettings::InferSubcommands)
         .arg(
             Arg::with_name("INPUT.pld")
                 .help("Input file")
@@ -54,15 +58,118 @@ fn main() {
                 .takes_value(false)
                 .help("Disable .pin file output"),
         )
+        .arg(
+            Arg::with_name("truthtable")
+                .short("t")
+                .long("truthtable")
+                .takes_value(false)
+                .help("Enable .tt truth table output"),
+        )
+        .arg(
+            Arg::with_name("nominimise")
+                .short("m")
+                .long("nominimise")
+                .takes_value(false)
+                .help("Disable logic minimisation, for galasm-identical output"),
+        )
+        .arg(
+            Arg::with_name("autopolarity")
+                .short("a")
+                .long("autopolarity")
+                .takes_value(false)
+                .help("Invert outputs where that's needed to fit the equations"),
+        )
+        .arg(
+            Arg::with_name("vectors")
+                .short("v")
+                .long("vectors")
+                .takes_value(false)
+                .help("Write the test vectors into the .jed file"),
+        )
+        .arg(
+            Arg::with_name("ptdisable")
+                .short("d")
+                .long("ptdisable")
+                .takes_value(false)
+                .help("Disable unused product terms on the GAL16V8 and GAL20V8"),
+        )
+        .arg(
+            Arg::with_name("split")
+                .short("x")
+                .long("split")
+                .takes_value(false)
+                .help("Split equations that are too big across spare outputs"),
+        )
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Disassemble a JEDEC file into equations, written to stdout")
+                .arg(
+                    Arg::with_name("INPUT.jed")
+                        .help("Input file")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pal2gal")
+                .about(
+                    "Convert a PAL's JEDEC file into one for a replacement GAL, written to stdout",
+                )
+                .arg(
+                    Arg::with_name("PAL")
+                        .help("Type of PAL (PAL16L8, PAL16R4, ..., PAL20R8)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("INPUT.jed")
+                        .help("Input file")
+                        .required(true)
+                        .index(2),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("disasm") {
+        match galette::disassemble(matches.value_of("INPUT.jed").unwrap()) {
+            Ok(pld) => print!("{}", pld),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("pal2gal") {
+        let pal_name = matches.value_of("PAL").unwrap();
+        let pal = PAL::from_name(pal_name).unwrap_or_else(|| {
+            eprintln!("Unknown PAL type: {}", pal_name);
+            process::exit(1);
+        });
+        match galette::convert_pal(matches.value_of("INPUT.jed").unwrap(), pal) {
+            Ok(jed) => print!("{}", jed),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     let file_name = matches.value_of("INPUT.pld").unwrap();
 
     let config = writer::Config {
         gen_fuse: !matches.is_present("nofuse"),
         gen_chip: !matches.is_present("nochip"),
         gen_pin: !matches.is_present("nopin"),
+        gen_tt: matches.is_present("truthtable"),
         jedec_sec_bit: matches.is_present("secure"),
+        minimise: !matches.is_present("nominimise"),
+        auto_polarity: matches.is_present("autopolarity"),
+        jedec_vectors: matches.is_present("vectors"),
+        pt_disable: matches.is_present("ptdisable"),
+        split: matches.is_present("split"),
     };
 
     if let Err(e) = galette::assemble(file_name, &config) {
@@ -0,0 +1,310 @@
+//
+// minimiser.rs: Logic minimisation
+//
+// Equations are copied into the fuse map product-by-product, so
+// redundant or mergeable products (e.g. "A * B + A * /B") use up
+// precious rows. This module reduces a Term to a smaller, equivalent
+// sum-of-products.
+//
+// The approach is a cut-down version of Espresso's: each product is
+// expanded into a prime implicant by dropping any inputs it doesn't
+// need, and then products covered by the rest of the sum are removed.
+// The result is prime and irredundant, which is usually minimal, and
+// always close.
+//
+
+use crate::gal::{Pin, Term};
+
+// A 'Cube' is a product term over a set of variables (numbered
+// 0..64). 'mask' says which variables appear in the product, and
+// 'val' gives their polarity (set for non-negated).
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cube {
+    mask: u64,
+    val: u64,
+}
+
+impl Cube {
+    // Does this cube contain (i.e. is it implied by) the other cube?
+    fn contains(&self, other: &Cube) -> bool {
+        self.mask & other.mask == self.mask && (self.val ^ other.val) & self.mask == 0
+    }
+
+    // Restrict the cube to the subspace given by 'by', returning None
+    // if the two don't intersect.
+    fn cofactor(&self, by: &Cube) -> Option<Cube> {
+        let common = self.mask & by.mask;
+        if (self.val ^ by.val) & common != 0 {
+            None
+        } else {
+            Some(Cube {
+                mask: self.mask & !by.mask,
+                val: self.val & !by.mask,
+            })
+        }
+    }
+}
+
+// Minimise a Term, returning an equivalent Term with as few products
+// as we can manage.
+pub fn minimise(term: &Term) -> Term {
+    let (vars, cover) = to_cubes(term);
+    from_cubes(term, &vars, &minimise_cubes(&cover))
+}
+
+// Returns the complement of a Term, as a minimised sum-of-products.
+pub fn complement(term: &Term) -> Term {
+    let (vars, cover) = to_cubes(term);
+    from_cubes(term, &vars, &minimise_cubes(&complement_cubes(&cover)))
+}
+
+////////////////////////////////////////////////////////////////////////
+// Conversion between Terms and cubes.
+//
+
+// Convert a Term to cubes, returning the list of pins used as
+// variables. Contradictory products (e.g. "A * /A") are dropped.
+fn to_cubes(term: &Term) -> (Vec<usize>, Vec<Cube>) {
+    let mut vars = Vec::new();
+    let mut cover = Vec::new();
+
+    'products: for product in term.pins.iter() {
+        let mut cube = Cube { mask: 0, val: 0 };
+        for pin in product.iter() {
+            let var = match vars.iter().position(|v| *v == pin.pin) {
+                Some(var) => var,
+                None => {
+                    vars.push(pin.pin);
+                    vars.len() - 1
+                }
+            };
+            let bit = 1 << var;
+            let val = if pin.neg { 0 } else { bit };
+            if cube.mask & bit != 0 && cube.val & bit != val {
+                continue 'products;
+            }
+            cube.mask |= bit;
+            cube.val |= val;
+        }
+        cover.push(cube);
+    }
+
+    (vars, cover)
+}
+
+fn from_cubes(term: &Term, vars: &[usize], cover: &[Cube]) -> Term {
+    let pins = cover
+        .iter()
+        .map(|cube| {
+            vars.iter()
+                .enumerate()
+                .filter(|(var, _)| cube.mask & 1 << var != 0)
+                .map(|(var, pin)| Pin {
+                    pin: *pin,
+                    neg: cube.val & 1 << var == 0,
+                })
+                .collect()
+        })
+        .collect();
+
+    Term {
+        line_num: term.line_num,
+        pins,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// The minimisation algorithm itself.
+//
+
+fn minimise_cubes(cover: &[Cube]) -> Vec<Cube> {
+    // Expand each cube to a prime implicant, dropping literals while
+    // the cube is still covered by the original function.
+    let mut expanded: Vec<Cube> = Vec::new();
+    for cube in cover.iter() {
+        let mut cube = *cube;
+        for var in 0..64 {
+            let bit = 1 << var;
+            if cube.mask & bit == 0 {
+                continue;
+            }
+            let candidate = Cube {
+                mask: cube.mask & !bit,
+                val: cube.val & !bit,
+            };
+            if covers(cover, &candidate) {
+                cube = candidate;
+            }
+        }
+        // Drop anything already covered by a single earlier cube, and
+        // anything this cube makes redundant.
+        if !expanded.iter().any(|c| c.contains(&cube)) {
+            expanded.retain(|c| !cube.contains(c));
+            expanded.push(cube);
+        }
+    }
+
+    // Remove cubes that are covered by the rest of the cover.
+    let mut i = expanded.len();
+    while i > 0 {
+        i -= 1;
+        let cube = expanded.remove(i);
+        if !covers(&expanded, &cube) {
+            expanded.insert(i, cube);
+        }
+    }
+
+    expanded
+}
+
+// Is the cube entirely covered by the cover?
+fn covers(cover: &[Cube], cube: &Cube) -> bool {
+    let cofactors = cover
+        .iter()
+        .filter_map(|c| c.cofactor(cube))
+        .collect::<Vec<_>>();
+    is_tautology(&cofactors)
+}
+
+fn is_tautology(cover: &[Cube]) -> bool {
+    if cover.iter().any(|c| c.mask == 0) {
+        return true;
+    }
+    if cover.is_empty() {
+        return false;
+    }
+
+    // Split on the most commonly-used variable.
+    let var = most_used_var(cover);
+    let bit = 1 << var;
+    let pos_used = cover.iter().any(|c| c.mask & bit != 0 && c.val & bit != 0);
+    let neg_used = cover.iter().any(|c| c.mask & bit != 0 && c.val & bit == 0);
+
+    if !(pos_used && neg_used) {
+        // The function is unate in this variable, so only the cubes
+        // that don't depend on it can make a tautology.
+        let rest = cover
+            .iter()
+            .filter(|c| c.mask & bit == 0)
+            .cloned()
+            .collect::<Vec<_>>();
+        return is_tautology(&rest);
+    }
+
+    [0, bit].iter().all(|val| {
+        let split = Cube {
+            mask: bit,
+            val: *val,
+        };
+        let cofactors = cover
+            .iter()
+            .filter_map(|c| c.cofactor(&split))
+            .collect::<Vec<_>>();
+        is_tautology(&cofactors)
+    })
+}
+
+// Find the complement of a cover by recursive Shannon expansion.
+fn complement_cubes(cover: &[Cube]) -> Vec<Cube> {
+    if cover.is_empty() {
+        return vec![Cube { mask: 0, val: 0 }];
+    }
+    if cover.iter().any(|c| c.mask == 0) {
+        return Vec::new();
+    }
+
+    let var = most_used_var(cover);
+    let bit = 1 << var;
+    let mut res = Vec::new();
+    for val in [0, bit].iter() {
+        let split = Cube {
+            mask: bit,
+            val: *val,
+        };
+        let cofactors = cover
+            .iter()
+            .filter_map(|c| c.cofactor(&split))
+            .collect::<Vec<_>>();
+        for cube in complement_cubes(&cofactors) {
+            res.push(Cube {
+                mask: cube.mask | bit,
+                val: cube.val | val,
+            });
+        }
+    }
+    res
+}
+
+fn most_used_var(cover: &[Cube]) -> usize {
+    (0..64)
+        .max_by_key(|var| {
+            let count = cover.iter().filter(|c| c.mask & 1 << var != 0).count();
+            // Prefer lower-numbered variables on a tie.
+            (count, std::cmp::Reverse(*var))
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pin(pin: usize, neg: bool) -> Pin {
+        Pin { pin, neg }
+    }
+
+    fn term(pins: Vec<Vec<Pin>>) -> Term {
+        Term { line_num: 0, pins }
+    }
+
+    #[test]
+    fn merges_adjacent_products() {
+        let input = term(vec![
+            vec![pin(2, false), pin(3, false)],
+            vec![pin(2, false), pin(3, true)],
+        ]);
+        assert_eq!(minimise(&input), term(vec![vec![pin(2, false)]]));
+    }
+
+    #[test]
+    fn removes_redundant_products() {
+        // A * B + /A * C + B * C => A * B + /A * C
+        let input = term(vec![
+            vec![pin(2, false), pin(3, false)],
+            vec![pin(2, true), pin(4, false)],
+            vec![pin(3, false), pin(4, false)],
+        ]);
+        assert_eq!(
+            minimise(&input),
+            term(vec![
+                vec![pin(2, false), pin(3, false)],
+                vec![pin(2, true), pin(4, false)],
+            ])
+        );
+    }
+
+    #[test]
+    fn drops_contradictions() {
+        let input = term(vec![vec![pin(2, false), pin(2, true)], vec![pin(3, false)]]);
+        assert_eq!(minimise(&input), term(vec![vec![pin(3, false)]]));
+    }
+
+    #[test]
+    fn finds_tautology() {
+        let input = term(vec![vec![pin(2, false)], vec![pin(2, true)]]);
+        assert_eq!(minimise(&input), term(vec![vec![]]));
+    }
+
+    #[test]
+    fn complements() {
+        // /(A * B) => /A + /B
+        let input = term(vec![vec![pin(2, false), pin(3, false)]]);
+        assert_eq!(
+            complement(&input),
+            term(vec![vec![pin(2, true)], vec![pin(3, true)]])
+        );
+        // Complement of false is true, and vice versa.
+        assert_eq!(complement(&term(vec![])), term(vec![vec![]]));
+        assert_eq!(complement(&term(vec![vec![]])), term(vec![]));
+    }
+}
@@ -0,0 +1,114 @@
+//
+// pal.rs: Fixed-function PAL architectures
+//
+// The GAL16V8 and GAL20V8 were designed as drop-in replacements for
+// the common fixed PALs. This file describes the PALs they can stand
+// in for, so that a design can be held to the PAL's fixed outputs,
+// and a PAL's fuse map can be converted to the GAL's.
+//
+
+use crate::{
+    chips::Chip,
+    gal::{Mode, GAL},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PAL {
+    PAL16L8,
+    PAL16R4,
+    PAL16R6,
+    PAL16R8,
+    PAL20L8,
+    PAL20R4,
+    PAL20R6,
+    PAL20R8,
+}
+
+impl PAL {
+    pub fn from_name(name: &str) -> Option<PAL> {
+        match name {
+            "PAL16L8" => Some(PAL::PAL16L8),
+            "PAL16R4" => Some(PAL::PAL16R4),
+            "PAL16R6" => Some(PAL::PAL16R6),
+            "PAL16R8" => Some(PAL::PAL16R8),
+            "PAL20L8" => Some(PAL::PAL20L8),
+            "PAL20R4" => Some(PAL::PAL20R4),
+            "PAL20R6" => Some(PAL::PAL20R6),
+            "PAL20R8" => Some(PAL::PAL20R8),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PAL::PAL16L8 => "PAL16L8",
+            PAL::PAL16R4 => "PAL16R4",
+            PAL::PAL16R6 => "PAL16R6",
+            PAL::PAL16R8 => "PAL16R8",
+            PAL::PAL20L8 => "PAL20L8",
+            PAL::PAL20R4 => "PAL20R4",
+            PAL::PAL20R6 => "PAL20R6",
+            PAL::PAL20R8 => "PAL20R8",
+        }
+    }
+
+    // The GAL that replaces it.
+    pub fn chip(&self) -> Chip {
+        match self {
+            PAL::PAL16L8 | PAL::PAL16R4 | PAL::PAL16R6 | PAL::PAL16R8 => Chip::GAL16V8,
+            PAL::PAL20L8 | PAL::PAL20R4 | PAL::PAL20R6 | PAL::PAL20R8 => Chip::GAL20V8,
+        }
+    }
+
+    fn num_registered(&self) -> usize {
+        match self {
+            PAL::PAL16L8 | PAL::PAL20L8 => 0,
+            PAL::PAL16R4 | PAL::PAL20R4 => 4,
+            PAL::PAL16R6 | PAL::PAL20R6 => 6,
+            PAL::PAL16R8 | PAL::PAL20R8 => 8,
+        }
+    }
+
+    // The GAL mode that matches the PAL's pinout: the registered PALs
+    // have a clock on pin 1 and /OE on pin 11 or 13, the others
+    // have tristate outputs with a product term enable.
+    pub fn mode(&self) -> Mode {
+        if self.num_registered() == 0 {
+            Mode::Complex
+        } else {
+            Mode::Registered
+        }
+    }
+
+    // The registered outputs are the ones in the middle, with the
+    // combinatorial outputs either side of them.
+    pub fn is_registered(&self, olmc_num: usize) -> bool {
+        let num_olmcs = self.chip().num_olmcs();
+        let first = (num_olmcs - self.num_registered()) / 2;
+        (first..first + self.num_registered()).contains(&olmc_num)
+    }
+
+    // Build the GAL that behaves the same as a PAL with the given
+    // fuses. The GAL's AND array was designed to match the PAL's in
+    // the corresponding mode, so the fuses are copied straight over,
+    // and the rest is fixed by the PAL type.
+    pub fn make_gal(&self, fuses: &[bool]) -> GAL {
+        let mut gal = GAL::new(self.chip());
+        gal.fuses.copy_from_slice(fuses);
+        gal.set_mode(self.mode());
+
+        let num_olmcs = self.chip().num_olmcs();
+        for i in 0..num_olmcs {
+            // All outputs are active low, and the combinatorial ones
+            // are tristate, with an enable term.
+            gal.xor[num_olmcs - 1 - i] = false;
+            gal.ac1[num_olmcs - 1 - i] = !self.is_registered(i);
+        }
+        // All the product terms are in use.
+        for bit in gal.pt.iter_mut() {
+            *bit = true;
+        }
+
+        gal
+    }
+}
@@ -7,12 +7,13 @@
 // present, but try to leave other checks for later in the pipeline.
 //
 
-use std::{collections::HashMap, fs, iter::Peekable};
+use std::{collections::HashMap, convert::TryFrom, fs, iter::Peekable};
 
 use crate::{
     chips::Chip,
     errors::{at_line, Error, ErrorCode, LineNum},
-    gal::Pin,
+    gal::{Mode, Pin},
+    pal::PAL,
 };
 
 ////////////////////////////////////////////////////////////////////////
@@ -22,16 +23,131 @@ use crate::{
 pub struct Content {
     pub chip: Chip,
     pub sig: Vec<u8>,
+    pub directives: Directives,
     pub pins: Vec<String>,
     pub eqns: Vec<Equation>,
+    pub vectors: Vec<Vector>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Equation {
     pub line_num: LineNum,
     pub lhs: LHS,
-    pub rhs: Vec<Pin>,
-    pub is_or: Vec<bool>,
+    pub rhs: Expr,
+}
+
+// 'Expr' is the parsed right-hand side of an equation. It keeps the
+// structure the user wrote (parentheses, grouped negation), and is
+// flattened into a sum-of-products 'Term' by the blueprint stage.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Pin(Pin),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    // True when the value on the pins, most significant first, is
+    // from 'lo' to 'hi' inclusive.
+    Range { pins: Vec<Pin>, lo: u64, hi: u64 },
+}
+
+impl Expr {
+    // All the pins referenced by the expression, in order of appearance.
+    pub fn pins(&self) -> Vec<Pin> {
+        let mut res = Vec::new();
+        self.collect_pins(&mut res);
+        res
+    }
+
+    // Does the expression contain a range comparison?
+    pub fn has_range(&self) -> bool {
+        match self {
+            Expr::Pin(_) => false,
+            Expr::Not(expr) => expr.has_range(),
+            Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().any(Expr::has_range),
+            Expr::Xor(lhs, rhs) => lhs.has_range() || rhs.has_range(),
+            Expr::Range { .. } => true,
+        }
+    }
+
+    fn collect_pins(&self, res: &mut Vec<Pin>) {
+        match self {
+            Expr::Pin(pin) => res.push(*pin),
+            Expr::Not(expr) => expr.collect_pins(res),
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs.iter() {
+                    expr.collect_pins(res);
+                }
+            }
+            Expr::Xor(lhs, rhs) => {
+                lhs.collect_pins(res);
+                rhs.collect_pins(res);
+            }
+            Expr::Range { pins, .. } => res.extend_from_slice(pins),
+        }
+    }
+}
+
+// Settings from the directive lines between the signature and the
+// pins. Directives start with '%', so that a pin list can't be taken
+// for one just because its first pin is named after a keyword.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directives {
+    // GAL16V8 and GAL20V8 only: "%TARGET PAL16R4" etc. restricts the
+    // design to what the PAL could do.
+    pub pal: Option<PAL>,
+    // GAL16V8 and GAL20V8 only: "%MODE SIMPLE" etc. overrides the mode
+    // that would be picked based on the equations.
+    pub mode: Option<Mode>,
+    // "%SIG" overrides the signature line.
+    pub sig: Option<Signature>,
+    // ATF22V10C only: "%UES OFF" leaves the signature out of the
+    // fuse map.
+    pub ues: bool,
+    // "%INPUTS" and "%OUTPUTS" declare signals that galette picks the
+    // pins for.
+    pub signals: Vec<Signal>,
+}
+
+// The signature can be given as a 64-bit hex number ("%SIG 0x..."),
+// up to 8 characters of text ("%SIG "..."") or as the CRC-32 of the
+// source file, in hex ("%SIG CRC"), to identify the build.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Signature {
+    Bytes(Vec<u8>),
+    SourceCRC,
+}
+
+// A signal declared without a pin. Until the placer finds it one,
+// it's given a pin number after the chip's real pins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub line_num: LineNum,
+    pub name: String,
+    pub neg: bool,
+    pub output: bool,
+}
+
+impl Default for Directives {
+    fn default() -> Self {
+        Directives {
+            pal: None,
+            mode: None,
+            sig: None,
+            ues: true,
+            signals: Vec::new(),
+        }
+    }
+}
+
+// A test vector, with one character per pin, as in a JEDEC 'V'
+// field: '0' and '1' drive an input, 'C' pulses it low-high-low,
+// 'H', 'L' and 'Z' give the expected output level, and 'X' and 'N'
+// (for the power pins) aren't checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vector {
+    pub line_num: LineNum,
+    pub values: String,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -64,9 +180,22 @@ const EOF_LINE: LineNum = 0;
 #[derive(Debug, Eq, PartialEq)]
 enum Token {
     Item((NamedPin, Suffix)),
+    Bus(NamedBus),
+    Number(u64),
     Equals,
+    // Comparison of a bus with a constant, written "==" or "!=".
+    IsEqual,
+    IsNotEqual,
+    // The range in a comparison, written ":[lo..hi]".
+    Range(u64, u64),
     And,
     Or,
+    Xor,
+    Xnor,
+    // Negation of a parenthesised group, written "/(".
+    Not,
+    LParen,
+    RParen,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -75,6 +204,33 @@ struct NamedPin {
     neg: bool,
 }
 
+// A bus, "A[3..0]", is a group of pins that share a name, and are
+// numbered by bit, listed from the first bit written to the last.
+#[derive(Debug, Eq, PartialEq)]
+struct NamedBus {
+    name: String,
+    neg: bool,
+    bits: Vec<usize>,
+}
+
+impl NamedBus {
+    fn pin_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.bits
+            .iter()
+            .map(move |bit| format!("{}{}", self.name, bit))
+    }
+}
+
+// A bus can't have more bits than the chip has pins.
+fn check_bus_width(chip: Chip, bus: &NamedBus) -> Result<(), ErrorCode> {
+    if bus.bits.len() > chip.num_pins() {
+        return Err(ErrorCode::BusTooWide {
+            max: chip.num_pins(),
+        });
+    }
+    Ok(())
+}
+
 ////////////////////////////////////////////////////////////////////////
 // Input tokenisation
 //
@@ -88,7 +244,12 @@ fn tokenise((line_num, s): (LineNum, &str)) -> Result<Vec<(LineNum, Token)>, Err
             Some(c) => match c {
                 '=' => {
                     chars.next();
-                    res.push((line_num, Token::Equals));
+                    if chars.peek() == Some(&'=') {
+                        chars.next();
+                        res.push((line_num, Token::IsEqual));
+                    } else {
+                        res.push((line_num, Token::Equals));
+                    }
                 }
                 '+' | '#' => {
                     chars.next();
@@ -98,8 +259,43 @@ fn tokenise((line_num, s): (LineNum, &str)) -> Result<Vec<(LineNum, Token)>, Err
                     chars.next();
                     res.push((line_num, Token::And));
                 }
-                '/' => res.push(tokenise_pin(line_num, &mut chars)?),
-                c if c.is_ascii_alphabetic() => res.push(tokenise_pin(line_num, &mut chars)?),
+                '$' => {
+                    chars.next();
+                    res.push((line_num, Token::Xor));
+                }
+                '!' => {
+                    // Only used as part of XNOR, "!$", and "!=".
+                    chars.next();
+                    match chars.next() {
+                        Some('$') => res.push((line_num, Token::Xnor)),
+                        Some('=') => res.push((line_num, Token::IsNotEqual)),
+                        _ => return err(line_num, ErrorCode::BadChar),
+                    }
+                }
+                ':' => {
+                    chars.next();
+                    res.push(tokenise_range(line_num, &mut chars)?);
+                }
+                '(' => {
+                    chars.next();
+                    res.push((line_num, Token::LParen));
+                }
+                ')' => {
+                    chars.next();
+                    res.push((line_num, Token::RParen));
+                }
+                '/' => {
+                    chars.next();
+                    if chars.peek() == Some(&'(') {
+                        res.push((line_num, Token::Not));
+                    } else {
+                        res.push(tokenise_pin(line_num, true, &mut chars)?);
+                    }
+                }
+                c if c.is_ascii_alphabetic() => {
+                    res.push(tokenise_pin(line_num, false, &mut chars)?)
+                }
+                c if c.is_ascii_digit() => res.push(tokenise_number(line_num, &mut chars)?),
                 c if c.is_whitespace() => {
                     chars.next();
                 }
@@ -110,19 +306,17 @@ fn tokenise((line_num, s): (LineNum, &str)) -> Result<Vec<(LineNum, Token)>, Err
     }
 }
 
-// Tokenise a single pin name.
-fn tokenise_pin<I>(line_num: LineNum, chars: &mut Peekable<I>) -> Result<(LineNum, Token), Error>
+// Tokenise a single pin name. Any negation prefix has already been
+// consumed, and is passed in as 'neg'.
+fn tokenise_pin<I>(
+    line_num: LineNum,
+    neg: bool,
+    chars: &mut Peekable<I>,
+) -> Result<(LineNum, Token), Error>
 where
     I: Iterator<Item = char>,
 {
     let mut name = String::new();
-    let mut neg = false;
-
-    // Look for a negation prefix.
-    if chars.peek() == Some(&'/') {
-        chars.next();
-        neg = true;
-    }
 
     // First character must be alphabetic
     match chars.peek().cloned() {
@@ -144,6 +338,18 @@ where
         }
     }
 
+    // A bus, "A[3..0]" or "A[3:0]", stands for the pins A3 to A0. A
+    // single bit, "A[3]", is just the pin A3.
+    if chars.peek() == Some(&'[') {
+        chars.next();
+        let bits = at_line(line_num, tokenise_bus_range(chars))?;
+        if let [bit] = bits[..] {
+            name.push_str(&bit.to_string());
+        } else {
+            return Ok((line_num, Token::Bus(NamedBus { name, neg, bits })));
+        }
+    }
+
     let named_pin = NamedPin { name, neg };
 
     // Look for extension
@@ -166,6 +372,102 @@ where
     Ok((line_num, Token::Item((named_pin, suffix))))
 }
 
+// Bus comparisons are done on 64-bit values, so no bus can be wider.
+const MAX_BUS_WIDTH: usize = 64;
+
+// Tokenise the bit range of a bus, after the '['.
+fn tokenise_bus_range<I>(chars: &mut Peekable<I>) -> Result<Vec<usize>, ErrorCode>
+where
+    I: Iterator<Item = char>,
+{
+    let mut range = String::new();
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some(c) => range.push(c),
+            None => return Err(ErrorCode::BadBus),
+        }
+    }
+
+    let parse_bit = |s: &str| s.trim().parse::<usize>().map_err(|_| ErrorCode::BadBus);
+    let (from, to) = match range.split_once("..").or_else(|| range.split_once(':')) {
+        Some((from, to)) => (parse_bit(from)?, parse_bit(to)?),
+        None => {
+            let bit = parse_bit(&range)?;
+            (bit, bit)
+        }
+    };
+    // Check before the bits are listed, as the numbers may be huge.
+    if from.abs_diff(to) >= MAX_BUS_WIDTH {
+        return Err(ErrorCode::BusTooWide { max: MAX_BUS_WIDTH });
+    }
+    // Ranges are matched against 64-bit values, so higher bits can't
+    // be addressed.
+    if from.max(to) >= MAX_BUS_WIDTH {
+        return Err(ErrorCode::BusBitTooHigh {
+            max: MAX_BUS_WIDTH - 1,
+        });
+    }
+
+    Ok(if from >= to {
+        (to..=from).rev().collect()
+    } else {
+        (from..=to).collect()
+    })
+}
+
+// Tokenise a range of values, in hex, after the ':'.
+fn tokenise_range<I>(line_num: LineNum, chars: &mut Peekable<I>) -> Result<(LineNum, Token), Error>
+where
+    I: Iterator<Item = char>,
+{
+    if chars.next() != Some('[') {
+        return err(line_num, ErrorCode::BadRange);
+    }
+    let mut range = String::new();
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some(c) => range.push(c),
+            None => return err(line_num, ErrorCode::BadRange),
+        }
+    }
+
+    let parse_hex = |s: &str| u64::from_str_radix(s.trim(), 16).ok();
+    match range.split_once("..") {
+        Some((lo, hi)) => match (parse_hex(lo), parse_hex(hi)) {
+            (Some(lo), Some(hi)) if lo <= hi => Ok((line_num, Token::Range(lo, hi))),
+            _ => err(line_num, ErrorCode::BadRange),
+        },
+        None => err(line_num, ErrorCode::BadRange),
+    }
+}
+
+// Tokenise a constant: decimal, or hex or binary with a "0x" or "0b"
+// prefix.
+fn tokenise_number<I>(line_num: LineNum, chars: &mut Peekable<I>) -> Result<(LineNum, Token), Error>
+where
+    I: Iterator<Item = char>,
+{
+    let mut s = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric()) {
+        s.push(c);
+    }
+
+    let value = if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2)
+    } else {
+        s.parse()
+    };
+
+    match value {
+        Ok(value) => Ok((line_num, Token::Number(value))),
+        Err(_) => err(line_num, ErrorCode::BadNumber),
+    }
+}
+
 fn ext_to_suffix(s: &str) -> Result<Suffix, ErrorCode> {
     Ok(match s {
         "T" => Suffix::T,
@@ -189,7 +491,17 @@ where
     type TokItem = Result<Vec<(LineNum, Token)>, Error>;
 
     fn has_continuation(v: &[(LineNum, Token)]) -> bool {
-        matches!(v.last(), Some((_, Token::And)) | Some((_, Token::Or)))
+        matches!(
+            v.last(),
+            Some((_, Token::And))
+                | Some((_, Token::Or))
+                | Some((_, Token::Xor))
+                | Some((_, Token::Xnor))
+                | Some((_, Token::IsEqual))
+                | Some((_, Token::IsNotEqual))
+                | Some((_, Token::LParen))
+                | Some((_, Token::Not))
+        )
     }
 
     fn is_continuation<I>(iter: &mut Peekable<I>) -> bool
@@ -197,7 +509,14 @@ where
         I: Iterator<Item = TokItem>,
     {
         if let Some(Ok(line)) = iter.peek() {
-            matches!(line.first(), Some((_, Token::And)) | Some((_, Token::Or)))
+            matches!(
+                line.first(),
+                Some((_, Token::And))
+                    | Some((_, Token::Or))
+                    | Some((_, Token::Xor))
+                    | Some((_, Token::Xnor))
+                    | Some((_, Token::RParen))
+            )
         } else {
             false
         }
@@ -288,12 +607,12 @@ where
 {
     let mut pins = Vec::new();
     let line @ (line_num, _) = next_or_fail(line_iter, ErrorCode::BadEOF)?;
-    let tokens = tokenise(line)?;
-    let len = tokens.len();
-    for token in tokens.into_iter() {
+    for token in tokenise(line)?.into_iter() {
         match token {
-            (_, Token::Item((name, suffix))) if suffix == Suffix::None => {
-                pins.push((name.name, name.neg))
+            (_, Token::Item((name, Suffix::None))) => pins.push((name.name, name.neg)),
+            (line_num, Token::Bus(bus)) => {
+                at_line(line_num, check_bus_width(chip, &bus))?;
+                pins.extend(bus.pin_names().map(|name| (name, bus.neg)));
             }
             (line_num, Token::Item(_)) => return err(line_num, ErrorCode::BadPin),
             (line_num, _) => return err(line_num, ErrorCode::BadPin),
@@ -303,12 +622,13 @@ where
     // We test this afterwards in case there was a bad token
     // causing us to miscount. In that case, the earlier error
     // message willl be more useful.
-    if len != chip.num_pins() / 2 {
+    if pins.len() != chip.num_pins() / 2 {
         return err(line_num, ErrorCode::BadPinCount);
     }
 
     // Extend the pin map with the pins we've just defined.
-    at_line(line_num, extend_pin_map(pin_map, chip, row_num, &pins))?;
+    let first_pin = 1 + row_num * chip.num_pins() / 2;
+    at_line(line_num, extend_pin_map(pin_map, chip, first_pin, &pins))?;
 
     Ok(pins)
 }
@@ -322,10 +642,10 @@ fn lookup_pin(
         .get(pin_name.name.as_str())
         .ok_or_else(|| match pin_name.name.as_str() {
             "NC" => ErrorCode::BadNC,
-            "AR" if chip == Chip::GAL22V10 => ErrorCode::BadSpecial {
+            "AR" if chip.has_arsp() => ErrorCode::BadSpecial {
                 term: pin_name.name.parse().unwrap(),
             },
-            "SP" if chip == Chip::GAL22V10 => ErrorCode::BadSpecial {
+            "SP" if chip.has_arsp() => ErrorCode::BadSpecial {
                 term: pin_name.name.parse().unwrap(),
             },
             _ => ErrorCode::UnknownPin,
@@ -361,7 +681,7 @@ where
 {
     Ok(match iter.next() {
         Some((line_num, Token::Item((named_pin, suffix)))) => {
-            if chip == Chip::GAL22V10 && (named_pin.name == "AR" || named_pin.name == "SP") {
+            if chip.has_arsp() && (named_pin.name == "AR" || named_pin.name == "SP") {
                 if suffix != Suffix::None {
                     return err(
                         line_num,
@@ -396,7 +716,8 @@ where
 fn parse_equation<I>(
     chip: Chip,
     pin_map: &HashMap<String, Pin>,
-    tokens: &mut I,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
 ) -> Result<Equation, Error>
 where
     I: Iterator<Item = (LineNum, Token)>,
@@ -408,66 +729,314 @@ where
         return err(line_num, ErrorCode::NoEquals);
     }
 
-    let mut rhs = vec![parse_pin(chip, pin_map, tokens)?];
-    let mut is_or = vec![false];
+    let rhs = parse_expr(chip, pin_map, macros, tokens)?;
+
+    // Anything left over (e.g. an unmatched ')') is an error.
+    if let Some((token_line_num, _)) = tokens.next() {
+        return err(token_line_num, ErrorCode::BadToken);
+    }
+
+    Ok(Equation { line_num, lhs, rhs })
+}
+
+// A macro definition, "LET X = A * B", names an expression that isn't
+// tied to a pin. Later equations can use the name, and the expression
+// is substituted in, so a macro can only use pins and earlier macros.
+fn is_macro(tokens: &[(LineNum, Token)]) -> bool {
+    matches!(
+        tokens,
+        [(_, Token::Item((NamedPin { name, neg: false }, Suffix::None))), (_, Token::Item(_)), ..]
+            if name == "LET"
+    )
+}
+
+fn parse_macro<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
+) -> Result<(String, Expr), Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    // Skip the "LET".
+    tokens.next();
+
+    let name = match tokens.next() {
+        Some((_, Token::Item((NamedPin { name, neg: false }, Suffix::None)))) => name,
+        Some((line_num, _)) => return err(line_num, ErrorCode::BadMacro),
+        None => return err(EOF_LINE, ErrorCode::BadMacro),
+    };
+
+    let (line_num, eq_token) = next_or_fail(tokens, ErrorCode::BadEOF)?;
+    if eq_token != Token::Equals {
+        return err(line_num, ErrorCode::NoEquals);
+    }
+
+    if pin_map.contains_key(&name) || macros.contains_key(&name) {
+        return err(line_num, ErrorCode::RepeatedMacro { name });
+    }
+
+    let expr = parse_expr(chip, pin_map, macros, tokens)?;
 
+    if let Some((token_line_num, _)) = tokens.next() {
+        return err(token_line_num, ErrorCode::BadToken);
+    }
+
+    Ok((name, expr))
+}
+
+// The RHS is parsed by recursive descent. In order of increasing
+// precedence, we have XOR/XNOR (as in CUPL), OR, AND, and then negated
+// or parenthesised groups and pins.
+
+fn parse_expr<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
+) -> Result<Expr, Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    let mut expr = parse_sum(chip, pin_map, macros, tokens)?;
+    loop {
+        let is_xnor = match tokens.peek() {
+            Some((_, Token::Xor)) => false,
+            Some((_, Token::Xnor)) => true,
+            _ => return Ok(expr),
+        };
+        tokens.next();
+        let rhs = parse_sum(chip, pin_map, macros, tokens)?;
+        expr = Expr::Xor(Box::new(expr), Box::new(rhs));
+        if is_xnor {
+            expr = Expr::Not(Box::new(expr));
+        }
+    }
+}
+
+fn parse_sum<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
+) -> Result<Expr, Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    let mut terms = vec![parse_product(chip, pin_map, macros, tokens)?];
+    while let Some((_, Token::Or)) = tokens.peek() {
+        tokens.next();
+        terms.push(parse_product(chip, pin_map, macros, tokens)?);
+    }
+    Ok(collapse(terms, Expr::Or))
+}
+
+fn parse_product<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
+) -> Result<Expr, Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    let mut factors = vec![parse_factor(chip, pin_map, macros, tokens)?];
     loop {
-        match tokens.next() {
+        match tokens.peek() {
             Some((_, Token::And)) => {
-                is_or.push(false);
-                rhs.push(parse_pin(chip, pin_map, tokens)?);
+                tokens.next();
+                factors.push(parse_factor(chip, pin_map, macros, tokens)?);
             }
-            Some((_, Token::Or)) => {
-                is_or.push(true);
-                rhs.push(parse_pin(chip, pin_map, tokens)?);
+            // These end the product, and are dealt with further up.
+            Some((_, Token::Or))
+            | Some((_, Token::Xor))
+            | Some((_, Token::Xnor))
+            | Some((_, Token::RParen))
+            | None => break,
+            Some((token_line_num, _)) => return err(*token_line_num, ErrorCode::BadToken),
+        }
+    }
+    Ok(collapse(factors, Expr::And))
+}
+
+fn parse_factor<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    macros: &HashMap<String, Expr>,
+    tokens: &mut Peekable<I>,
+) -> Result<Expr, Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    match tokens.peek() {
+        Some((_, Token::Not)) => {
+            tokens.next();
+            let expr = parse_factor(chip, pin_map, macros, tokens)?;
+            Ok(Expr::Not(Box::new(expr)))
+        }
+        Some((paren_line_num, Token::LParen)) => {
+            let paren_line_num = *paren_line_num;
+            tokens.next();
+            let expr = parse_expr(chip, pin_map, macros, tokens)?;
+            match tokens.next() {
+                Some((_, Token::RParen)) => Ok(expr),
+                Some((token_line_num, _)) => err(token_line_num, ErrorCode::NoCloseParen),
+                None => err(paren_line_num, ErrorCode::NoCloseParen),
             }
-            Some((token_line_num, _)) => return err(token_line_num, ErrorCode::BadToken),
-            None => break,
         }
+        Some((_, Token::Bus(_))) => parse_comparison(chip, pin_map, tokens),
+        // Macros are inlined, negated if need be.
+        Some((_, Token::Item((named_pin, Suffix::None))))
+            if macros.contains_key(&named_pin.name) =>
+        {
+            let expr = macros[&named_pin.name].clone();
+            let neg = named_pin.neg;
+            tokens.next();
+            Ok(if neg { Expr::Not(Box::new(expr)) } else { expr })
+        }
+        _ => Ok(Expr::Pin(parse_pin(chip, pin_map, tokens)?)),
+    }
+}
+
+// A bus compared with a constant, "A[3..0] == 5", is the product of
+// the bus's pins, negated where the constant has a zero bit. The first
+// bit of the bus is the most significant. "!=" is the complement.
+//
+// A bus can also be checked against a range of values, in hex, as in
+// CUPL: "A[15..12]:[C000..DFFF]". Here, the bits of the values are
+// matched to the pins by the bit numbers, so that full addresses can
+// be given, as long as the range lines up with the bus's lowest bit.
+// The range is expanded into products by the blueprint stage.
+fn parse_comparison<I>(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    tokens: &mut Peekable<I>,
+) -> Result<Expr, Error>
+where
+    I: Iterator<Item = (LineNum, Token)>,
+{
+    let (line_num, bus) = match tokens.next() {
+        Some((line_num, Token::Bus(bus))) => (line_num, bus),
+        _ => return err(EOF_LINE, ErrorCode::BadToken),
+    };
+    at_line(line_num, check_bus_width(chip, &bus))?;
+    let neg = match tokens.next() {
+        Some((_, Token::IsEqual)) => false,
+        Some((_, Token::IsNotEqual)) => true,
+        Some((_, Token::Range(lo, hi))) => {
+            return at_line(line_num, range_to_expr(chip, pin_map, &bus, lo, hi))
+        }
+        _ => return err(line_num, ErrorCode::BareBus),
+    };
+    let value = match tokens.next() {
+        Some((_, Token::Number(value))) => value,
+        Some((token_line_num, _)) => return err(token_line_num, ErrorCode::BadToken),
+        None => return err(line_num, ErrorCode::BadEOL),
+    };
+
+    let width = bus.bits.len();
+    if value.checked_shr(width as u32).unwrap_or(0) != 0 {
+        return err(line_num, ErrorCode::BusValueTooBig { value, width });
+    }
+
+    let pins = bus
+        .pin_names()
+        .zip((0..width).rev())
+        .map(|(name, shift)| {
+            let bit_set = value.checked_shr(shift as u32).unwrap_or(0) & 1 != 0;
+            let named_pin = NamedPin {
+                name,
+                neg: bus.neg == bit_set,
+            };
+            lookup_pin(chip, pin_map, &named_pin).map(Expr::Pin)
+        })
+        .collect::<Result<Vec<Expr>, ErrorCode>>();
+    let expr = collapse(at_line(line_num, pins)?, Expr::And);
+    Ok(if neg { Expr::Not(Box::new(expr)) } else { expr })
+}
+
+fn range_to_expr(
+    chip: Chip,
+    pin_map: &HashMap<String, Pin>,
+    bus: &NamedBus,
+    lo: u64,
+    hi: u64,
+) -> Result<Expr, ErrorCode> {
+    let mut bits = bus.bits.clone();
+    bits.sort_unstable_by(|a, b| b.cmp(a));
+    let (max, min) = (bits[0], bits[bits.len() - 1]);
+
+    // The bits below the bus must cover the full block, and there
+    // must be nothing above it.
+    // Bus bits are checked when the bus is parsed, but don't let a
+    // shift overflow if one gets through.
+    let shift = |bit: usize| u32::try_from(bit).unwrap_or(u32::MAX);
+    let low_mask = 1u64.checked_shl(shift(min)).map_or(u64::MAX, |bit| bit - 1);
+    let fits = hi.checked_shr(shift(max).saturating_add(1)).unwrap_or(0) == 0;
+    if !fits || lo & low_mask != 0 || hi & low_mask != low_mask {
+        return Err(ErrorCode::RangeMisaligned { max, min });
     }
 
-    Ok(Equation {
-        line_num,
-        lhs,
-        rhs,
-        is_or,
+    let pins = bits
+        .iter()
+        .map(|bit| {
+            let named_pin = NamedPin {
+                name: format!("{}{}", bus.name, bit),
+                neg: bus.neg,
+            };
+            lookup_pin(chip, pin_map, &named_pin)
+        })
+        .collect::<Result<Vec<Pin>, ErrorCode>>()?;
+
+    Ok(Expr::Range {
+        pins,
+        lo: lo.checked_shr(shift(min)).unwrap_or(0),
+        hi: hi.checked_shr(shift(min)).unwrap_or(0),
     })
 }
 
+// Avoid wrapping single items in an AND or OR.
+fn collapse(mut exprs: Vec<Expr>, f: fn(Vec<Expr>) -> Expr) -> Expr {
+    if exprs.len() == 1 {
+        exprs.pop().unwrap()
+    } else {
+        f(exprs)
+    }
+}
+
 // Add a row's worth of pins to the pin map.
 fn extend_pin_map(
     pin_map: &mut HashMap<String, Pin>,
     chip: Chip,
-    row_num: usize,
+    first_pin: usize,
     pins: &[(String, bool)],
 ) -> Result<(), ErrorCode> {
-    let num_pins = chip.num_pins();
-    let first_pin = 1 + row_num * num_pins / 2;
     for ((name, neg), pin_num) in pins.iter().cloned().zip(first_pin..) {
-        if pin_num == num_pins && (name.as_str(), neg) != ("VCC", false) {
+        if pin_num == chip.vcc_pin() && (name.as_str(), neg) != ("VCC", false) {
             return Err(ErrorCode::InvalidPowerPinName {
                 pin: pin_num,
                 name: "VCC",
             });
         }
-        if pin_num == num_pins / 2 && (name.as_str(), neg) != ("GND", false) {
+        if pin_num == chip.gnd_pin() && (name.as_str(), neg) != ("GND", false) {
             return Err(ErrorCode::InvalidPowerPinName {
                 pin: pin_num,
                 name: "GND",
             });
         }
-        if name == "VCC" && pin_num != num_pins {
+        if name == "VCC" && pin_num != chip.vcc_pin() {
             return Err(ErrorCode::InvalidPowerPinLocation {
                 pin: pin_num,
                 name: "VCC",
-                expected_pin: num_pins,
+                expected_pin: chip.vcc_pin(),
             });
         }
-        if name == "GND" && pin_num != num_pins / 2 {
+        if name == "GND" && pin_num != chip.gnd_pin() {
             return Err(ErrorCode::InvalidPowerPinLocation {
                 pin: pin_num,
                 name: "GND",
-                expected_pin: num_pins / 2,
+                expected_pin: chip.gnd_pin(),
             });
         }
         if name != "NC" {
@@ -475,7 +1044,7 @@ fn extend_pin_map(
                 return Err(ErrorCode::RepeatedPinName { name });
             }
 
-            if chip == Chip::GAL22V10 {
+            if chip.has_arsp() {
                 // parse returns Ok if name is "AR" or "SP"
                 if let Ok(term) = name.parse() {
                     return Err(ErrorCode::ReservedPinName { term });
@@ -489,10 +1058,181 @@ fn extend_pin_map(
     Ok(())
 }
 
-fn parse_core<'a, I>(mut line_iter: I) -> Result<Content, Error>
+// A directive is '%' and a keyword on its own line, maybe with an
+// argument.
+fn is_directive(line: &str) -> bool {
+    line.starts_with('%')
+}
+
+fn parse_directives<'a, I>(chip: Chip, line_iter: &mut Peekable<I>) -> Result<Directives, Error>
 where
     I: Iterator<Item = (LineNum, &'a str)>,
 {
+    let mut directives = Directives::default();
+    while let Some((line_num, line)) = line_iter.next_if(|(_, line)| is_directive(line)) {
+        let line = &line[1..];
+        match line.split_whitespace().next() {
+            Some(name @ ("INPUTS" | "OUTPUTS")) => {
+                let output = name == "OUTPUTS";
+                let rest = (line_num, line[name.len()..].trim());
+                directives
+                    .signals
+                    .extend(parse_signals(chip, rest, output)?);
+            }
+            _ => at_line(line_num, set_directive(chip, &mut directives, line))?,
+        }
+    }
+    Ok(directives)
+}
+
+fn set_directive(chip: Chip, directives: &mut Directives, line: &str) -> Result<(), ErrorCode> {
+    let words = line.split_whitespace().collect::<Vec<&str>>();
+    let name = words.first().copied().unwrap_or_default().to_string();
+    let supported = match name.as_str() {
+        "TARGET" | "MODE" => chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8,
+        "SIG" => true,
+        "UES" => chip.has_optional_ues(),
+        _ => return Err(ErrorCode::UnknownDirective { name }),
+    };
+    if !supported {
+        return Err(ErrorCode::UnsupportedDirective { name });
+    }
+    match words[1..] {
+        // The signature text may contain spaces.
+        _ if name == "SIG" => {
+            directives.sig = Some(parse_sig_directive(line[name.len()..].trim())?)
+        }
+        ["ON"] if name == "UES" => directives.ues = true,
+        ["OFF"] if name == "UES" => directives.ues = false,
+        [pal] if name == "TARGET" => {
+            let pal = PAL::from_name(pal).ok_or(ErrorCode::BadDirectiveArg { name })?;
+            if pal.chip() != chip.arch() {
+                return Err(ErrorCode::PALWrongChip {
+                    pal: pal.name(),
+                    chip: pal.chip().name(),
+                });
+            }
+            directives.pal = Some(pal);
+        }
+        [mode] if name == "MODE" => {
+            directives.mode = Some(match mode {
+                "SIMPLE" => Mode::Simple,
+                "COMPLEX" => Mode::Complex,
+                "REGISTERED" => Mode::Registered,
+                _ => return Err(ErrorCode::BadDirectiveArg { name }),
+            });
+        }
+        _ => return Err(ErrorCode::BadDirectiveArg { name }),
+    }
+    // A PAL's mode is fixed.
+    if let (Some(pal), Some(mode)) = (directives.pal, directives.mode) {
+        if pal.mode() != mode {
+            return Err(ErrorCode::PALModeConflict { pal: pal.name() });
+        }
+    }
+    // Without the UES, there's nowhere to put the signature.
+    if directives.sig.is_some() && !directives.ues {
+        return Err(ErrorCode::SigWithoutUES);
+    }
+    Ok(())
+}
+
+// Read the names on an "%INPUTS" or "%OUTPUTS" line, which are written
+// as in the pin list, buses included.
+fn parse_signals(
+    chip: Chip,
+    line @ (line_num, _): (LineNum, &str),
+    output: bool,
+) -> Result<Vec<Signal>, Error> {
+    let mut names = Vec::new();
+    for token in tokenise(line)?.into_iter() {
+        match token {
+            (_, Token::Item((name, Suffix::None))) => names.push((name.name, name.neg)),
+            (line_num, Token::Bus(bus)) => {
+                at_line(line_num, check_bus_width(chip, &bus))?;
+                names.extend(bus.pin_names().map(|name| (name, bus.neg)));
+            }
+            (line_num, _) => return err(line_num, ErrorCode::BadPin),
+        }
+    }
+
+    if names.is_empty() {
+        let name = String::from(if output { "OUTPUTS" } else { "INPUTS" });
+        return err(line_num, ErrorCode::BadDirectiveArg { name });
+    }
+    // They need a real pin, which these aren't.
+    if names
+        .iter()
+        .any(|(name, _)| matches!(name.as_str(), "NC" | "VCC" | "GND"))
+    {
+        return err(line_num, ErrorCode::BadPin);
+    }
+
+    Ok(names
+        .into_iter()
+        .map(|(name, neg)| Signal {
+            line_num,
+            name,
+            neg,
+            output,
+        })
+        .collect())
+}
+
+fn parse_sig_directive(arg: &str) -> Result<Signature, ErrorCode> {
+    if arg == "CRC" {
+        return Ok(Signature::SourceCRC);
+    }
+    if let Some(hex) = arg.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16)
+            .map(|value| Signature::Bytes(value.to_be_bytes().to_vec()))
+            .map_err(|_| ErrorCode::BadSignature);
+    }
+    match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+        Some(text) if text.len() <= 8 && text.is_ascii() => {
+            Ok(Signature::Bytes(text.bytes().collect()))
+        }
+        _ => Err(ErrorCode::BadSignature),
+    }
+}
+
+// The standard CRC-32, as used by zip etc.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// Parse a test vector line. Whitespace may be used to group the
+// values.
+fn parse_vector(chip: Chip, (line_num, s): (LineNum, &str)) -> Result<Vector, Error> {
+    let values = s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    if let Some(c) = values.chars().find(|c| !"01CHLZXN".contains(*c)) {
+        return err(line_num, ErrorCode::BadVectorValue { value: c });
+    }
+    if values.len() != chip.num_pins() {
+        return err(
+            line_num,
+            ErrorCode::BadVectorLength {
+                expected: chip.num_pins(),
+                seen: values.len(),
+            },
+        );
+    }
+    Ok(Vector { line_num, values })
+}
+
+fn parse_core(data: &str) -> Result<Content, Error> {
+    let mut line_iter = (1..).zip(data.lines());
     let chip = parse_chip(&mut line_iter)?;
     let signature = parse_signature(&mut line_iter)?;
 
@@ -502,22 +1242,64 @@ where
     let mut line_iter = line_iter
         .map(|(i, x)| (i, str::trim(remove_comment(x))))
         .filter(|(_, x)| !x.is_empty())
-        .take_while(|(_, x)| *x != "DESCRIPTION");
+        .take_while(|(_, x)| *x != "DESCRIPTION")
+        .peekable();
+
+    let directives = parse_directives(chip, &mut line_iter)?;
+    let signature = match &directives.sig {
+        None => signature,
+        Some(Signature::Bytes(bytes)) => bytes.clone(),
+        Some(Signature::SourceCRC) => format!("{:08X}", crc32(data.as_bytes())).into_bytes(),
+    };
 
     let mut pin_map = HashMap::new();
     let mut pins = parse_pins(&mut pin_map, chip, 0, &mut line_iter)?;
     let mut pins2 = parse_pins(&mut pin_map, chip, 1, &mut line_iter)?;
     pins.append(&mut pins2);
 
+    // Signals without pins get pin numbers after the real ones.
+    for (signal, pin_num) in directives.signals.iter().zip(chip.num_pins() + 1..) {
+        let name = (signal.name.clone(), signal.neg);
+        at_line(
+            signal.line_num,
+            extend_pin_map(&mut pin_map, chip, pin_num, &[name]),
+        )?;
+    }
+
+    // The equations run up to an optional VECTORS line, after which
+    // come the test vectors.
+    let mut lines = line_iter.collect::<Vec<_>>();
+    let vector_lines = match lines.iter().position(|(_, x)| *x == "VECTORS") {
+        Some(i) => lines.split_off(i).split_off(1),
+        None => Vec::new(),
+    };
+
     // We tokenise the lines first, as the equation parser will want
     // to look ahead onto the token starting the next line (not yet
     // implemented).
     let mut equations = Vec::new();
-    for tokens_or_err in tokenised_lines(line_iter) {
+    let mut macros = HashMap::new();
+    for tokens_or_err in tokenised_lines(lines.into_iter()) {
         let tokens = tokens_or_err?;
-        equations.push(parse_equation(chip, &pin_map, &mut tokens.into_iter())?);
+        if is_macro(&tokens) {
+            let (name, expr) =
+                parse_macro(chip, &pin_map, &macros, &mut tokens.into_iter().peekable())?;
+            macros.insert(name, expr);
+        } else {
+            equations.push(parse_equation(
+                chip,
+                &pin_map,
+                &macros,
+                &mut tokens.into_iter().peekable(),
+            )?);
+        }
     }
 
+    let vectors = vector_lines
+        .into_iter()
+        .map(|line| parse_vector(chip, line))
+        .collect::<Result<Vec<Vector>, Error>>()?;
+
     // The rest of the pipeline just wants string names.
     let pin_names = pins
         .iter()
@@ -535,8 +1317,10 @@ where
     Ok(Content {
         chip,
         sig: signature,
+        directives,
         pins: pin_names,
         eqns: equations,
+        vectors,
     })
 }
 
@@ -549,7 +1333,7 @@ fn err<T>(line_num: LineNum, error_code: ErrorCode) -> Result<T, Error> {
 
 pub fn parse(file_name: &str) -> Result<Content, Error> {
     let data = fs::read_to_string(file_name).expect("Unable to read file");
-    parse_core((1..).zip(data.lines())).map_err(|e| {
+    parse_core(&data).map_err(|e| {
         if e.line == EOF_LINE {
             Error {
                 line: data.lines().count(),
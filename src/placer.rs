@@ -0,0 +1,259 @@
+//
+// placer.rs: Automatic pin assignment
+//
+// Signals declared on "%INPUTS" and "%OUTPUTS" lines, rather than in
+// the pin list, are given stand-in pin numbers by the parser. This
+// picks real pins for them from the ones named "NC", and rewrites the
+// equations to use them. Outputs need an OLMC with enough rows for
+// their equation, and on the GAL16V8 and GAL20V8 the mode rules some
+// pins out, so the placement follows the builder's rules. The builder
+// still checks the result, as usual.
+//
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    blueprint,
+    chips::Chip,
+    errors::{at_line, Error, ErrorCode},
+    gal::{Mode, Pin},
+    parser::{Content, Expr, Signal, Suffix, LHS},
+};
+
+// What the equations need from the pins, by pin number.
+struct Needs {
+    // The number of products in each output's main equation.
+    rows: HashMap<usize, usize>,
+    registered: HashSet<usize>,
+    tristate: bool,
+    // Pins used as inputs to equations, including feedback.
+    inputs: HashSet<usize>,
+}
+
+pub fn place(content: &mut Content) -> Result<(), Error> {
+    if content.directives.signals.is_empty() {
+        return Ok(());
+    }
+
+    let chip = content.chip;
+    let needs = find_needs(content)?;
+    let mode = match chip.arch() {
+        Chip::GAL16V8 | Chip::GAL20V8 => Some(choose_mode(content, &needs)),
+        _ => None,
+    };
+
+    // Stand-in pin numbers run on from the real ones.
+    let signals = content
+        .directives
+        .signals
+        .iter()
+        .zip(chip.num_pins() + 1..)
+        .collect::<Vec<_>>();
+
+    // Outputs go first, as they're fussier, biggest equation first,
+    // each into the smallest OLMC it fits. Failing that, it gets the
+    // biggest there is, and the builder reports it as too big.
+    let mut outputs = signals
+        .iter()
+        .filter(|(signal, _)| signal.output)
+        .collect::<Vec<_>>();
+    outputs.sort_by_key(|(_, pin)| std::cmp::Reverse(needs.rows.get(pin).copied().unwrap_or(0)));
+
+    let mut placed = HashMap::new();
+    for (signal, stand_in) in outputs {
+        let need = needs.rows.get(stand_in).copied().unwrap_or(0);
+        let registered = needs.registered.contains(stand_in);
+        let feedback = needs.inputs.contains(stand_in);
+        let pin = free_pins(content, &placed)
+            .filter(|pin| allowed(content, mode, *pin, Some(registered), feedback))
+            .min_by_key(|pin| {
+                let olmc_num = chip.pin_to_olmc(*pin).unwrap();
+                let rows = capacity(chip, mode, olmc_num, registered);
+                if rows >= need {
+                    (false, rows)
+                } else {
+                    (true, usize::MAX - rows)
+                }
+            });
+        placed.insert(*stand_in, choose(signal, pin)?);
+    }
+
+    // Inputs prefer the dedicated input pins, leaving the OLMCs free.
+    for (signal, stand_in) in signals.iter().filter(|(signal, _)| !signal.output) {
+        let pin = free_pins(content, &placed)
+            .filter(|pin| allowed(content, mode, *pin, None, true))
+            .min_by_key(|pin| chip.pin_to_olmc(*pin).is_some());
+        placed.insert(*stand_in, choose(signal, pin)?);
+    }
+
+    // Rewrite the design with the real pins.
+    for (signal, stand_in) in signals.iter() {
+        let name = if signal.neg {
+            format!("/{}", signal.name)
+        } else {
+            signal.name.clone()
+        };
+        content.pins[placed[stand_in] - 1] = name;
+    }
+    for eqn in content.eqns.iter_mut() {
+        if let LHS::Pin((ref mut pin, _)) = eqn.lhs {
+            move_pin(pin, &placed);
+        }
+        move_expr(&mut eqn.rhs, &placed);
+    }
+
+    Ok(())
+}
+
+fn find_needs(content: &Content) -> Result<Needs, Error> {
+    let mut needs = Needs {
+        rows: HashMap::new(),
+        registered: HashSet::new(),
+        tristate: false,
+        inputs: HashSet::new(),
+    };
+
+    for eqn in content.eqns.iter() {
+        needs
+            .inputs
+            .extend(eqn.rhs.pins().iter().map(|pin| pin.pin));
+        if let LHS::Pin((pin, suffix)) = eqn.lhs {
+            match suffix {
+                Suffix::None | Suffix::T | Suffix::R => {
+                    let term = at_line(eqn.line_num, blueprint::eqn_to_term(content.chip, eqn))?;
+                    needs.rows.insert(pin.pin, term.pins.len());
+                }
+                _ => {}
+            }
+            match suffix {
+                Suffix::R => {
+                    needs.registered.insert(pin.pin);
+                }
+                Suffix::T => needs.tristate = true,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(needs)
+}
+
+// Work out the mode the GAL16V8 or GAL20V8 will end up in, as
+// 'analyse_mode' in gal_builder.rs will see it once the signals are
+// placed. Outputs that are fed back need complex mode.
+fn choose_mode(content: &Content, needs: &Needs) -> Mode {
+    let directives = &content.directives;
+    match (directives.pal, directives.mode) {
+        (Some(pal), _) => pal.mode(),
+        (None, Some(mode)) => mode,
+        (None, None) if !needs.registered.is_empty() => Mode::Registered,
+        (None, None) if needs.tristate => Mode::Complex,
+        (None, None) => {
+            let is_middle = |pin: &usize| matches!(content.chip.pin_to_olmc(*pin), Some(3 | 4));
+            if needs
+                .inputs
+                .iter()
+                .any(|pin| needs.rows.contains_key(pin) || is_middle(pin))
+            {
+                Mode::Complex
+            } else {
+                Mode::Simple
+            }
+        }
+    }
+}
+
+fn free_pins<'a>(
+    content: &'a Content,
+    placed: &'a HashMap<usize, usize>,
+) -> impl Iterator<Item = usize> + 'a {
+    (1..=content.chip.num_pins())
+        .filter(move |pin| content.pins[pin - 1] == "NC" && !placed.values().any(|p| p == pin))
+}
+
+// Can the pin take the signal? 'registered' is None for an input, or
+// whether the output is registered, and 'feedback' is whether the
+// signal is used as an input to the equations.
+fn allowed(
+    content: &Content,
+    mode: Option<Mode>,
+    pin: usize,
+    registered: Option<bool>,
+    feedback: bool,
+) -> bool {
+    let chip = content.chip;
+    let directives = &content.directives;
+
+    let olmc_num = match chip.pin_to_olmc(pin) {
+        Some(olmc_num) => olmc_num,
+        None => {
+            // The GAL20RA10 keeps pins 1 and 13 for /PL and /OE, as
+            // does registered mode for the clock and /OE.
+            let reserved = chip.arch() == Chip::GAL20RA10 || mode == Some(Mode::Registered);
+            return registered.is_none()
+                && !(reserved && (pin == 1 || pin == chip.num_pins() / 2 + 1));
+        }
+    };
+
+    // A PAL's registered outputs are fixed, and can't be inputs.
+    if let Some(pal) = directives.pal {
+        if registered.unwrap_or(false) != pal.is_registered(olmc_num) {
+            return false;
+        }
+    }
+
+    let is_end = olmc_num == 0 || olmc_num == chip.num_olmcs() - 1;
+    match mode {
+        Some(Mode::Simple) => registered.is_some() || !matches!(olmc_num, 3 | 4),
+        Some(Mode::Complex) => !is_end || (registered.is_some() && !feedback),
+        _ => true,
+    }
+}
+
+// Rows for an output's main equation, as in 'adjust_main_bounds' in
+// gal_builder.rs.
+fn capacity(chip: Chip, mode: Option<Mode>, olmc_num: usize, registered: bool) -> usize {
+    let rows = chip.num_rows_for_olmc(olmc_num);
+    match mode {
+        Some(Mode::Simple) => rows,
+        Some(_) if registered => rows,
+        _ if chip.arch() == Chip::GAL20RA10 => rows - 4,
+        _ => rows - 1,
+    }
+}
+
+fn choose(signal: &Signal, pin: Option<usize>) -> Result<usize, Error> {
+    pin.ok_or_else(|| Error {
+        code: ErrorCode::NoFreePin {
+            name: signal.name.clone(),
+        },
+        line: signal.line_num,
+    })
+}
+
+fn move_pin(pin: &mut Pin, placed: &HashMap<usize, usize>) {
+    if let Some(new_pin) = placed.get(&pin.pin) {
+        pin.pin = *new_pin;
+    }
+}
+
+fn move_expr(expr: &mut Expr, placed: &HashMap<usize, usize>) {
+    match expr {
+        Expr::Pin(pin) => move_pin(pin, placed),
+        Expr::Not(expr) => move_expr(expr, placed),
+        Expr::And(exprs) | Expr::Or(exprs) => {
+            for expr in exprs.iter_mut() {
+                move_expr(expr, placed);
+            }
+        }
+        Expr::Xor(lhs, rhs) => {
+            move_expr(lhs, placed);
+            move_expr(rhs, placed);
+        }
+        Expr::Range { pins, .. } => {
+            for pin in pins.iter_mut() {
+                move_pin(pin, placed);
+            }
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//
+// sim.rs: Fuse map simulator
+//
+// This module simulates a GAL by reading the fuses directly - the
+// AND array, the XOR/polarity bits, the AC1 and mode bits, and the
+// product term enables - rather than looking at the equations it
+// was built from. That lets it act as an independent check on the
+// rest of the assembler.
+//
+// Outputs that feed back into the array are handled by evaluating
+// repeatedly until the pin levels settle. Registered outputs are
+// simulated by stepping through a sequence of inputs, with the
+// registers loaded on rising clock edges. The GAL20RA10's /PL
+// (register preload) pin isn't modelled.
+//
+// The test vectors from the .pld file are checked by running them
+// through the simulator in order.
+//
+
+use crate::{
+    chips::Chip,
+    errors::{at_line, Error, ErrorCode},
+    gal::{Mode, GAL},
+    parser::Vector,
+};
+
+// The level seen on a pin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Low,
+    High,
+    HighZ,
+}
+
+impl Level {
+    fn from_bool(bit: bool) -> Level {
+        if bit {
+            Level::High
+        } else {
+            Level::Low
+        }
+    }
+
+    // As used in test vectors.
+    pub(crate) fn to_char(self) -> char {
+        match self {
+            Level::Low => 'L',
+            Level::High => 'H',
+            Level::HighZ => 'Z',
+        }
+    }
+}
+
+// How an OLMC is configured, as read from the fuses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputMode {
+    // Not driven, so the pin is an input.
+    Input,
+    // Combinatorial, possibly with an enable term.
+    Combinatorial,
+    // Driven from the register.
+    Registered,
+}
+
+// Give up on outputs settling after this many passes.
+const MAX_PASSES: usize = 64;
+
+pub struct Simulator<'a> {
+    gal: &'a GAL,
+    // The Q output of each OLMC's flip-flop. All registers are reset
+    // at power-up.
+    regs: Vec<bool>,
+    // The clock signal for each OLMC and the value its register would
+    // load, as of the last step, so that we can spot rising edges and
+    // load the value from before the edge.
+    last: Option<(Vec<bool>, Vec<bool>)>,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(gal: &'a GAL) -> Self {
+        Simulator {
+            gal,
+            regs: vec![false; gal.chip.num_olmcs()],
+            last: None,
+        }
+    }
+
+    // Find the levels on each pin (indexed from pin 1), given the
+    // levels being driven onto the pins from outside. Values driven
+    // onto pins that are outputs are only seen when the output isn't
+    // enabled. The registers are left untouched.
+    pub fn eval(&self, inputs: &[bool]) -> Result<Vec<Level>, ErrorCode> {
+        let outputs = self.settle(inputs)?;
+        Ok(self.pin_levels_driven(inputs, &outputs))
+    }
+
+    // Like 'eval', but updating the registers: they're loaded on the
+    // rising edge of their clocks (compared to the previous step), and
+    // asynchronous resets and presets are applied.
+    pub fn step(&mut self, inputs: &[bool]) -> Result<Vec<Level>, ErrorCode> {
+        let num_olmcs = self.gal.chip.num_olmcs();
+
+        // Changing a register can clock another (e.g. a ripple
+        // counter on the GAL20RA10), so keep going until stable.
+        for _ in 0..MAX_PASSES {
+            let outputs = self.settle(inputs)?;
+            let pins = self.pin_levels(inputs, &outputs);
+            let clocks = (0..num_olmcs)
+                .map(|olmc_num| self.eval_clock(&pins, olmc_num))
+                .collect::<Vec<bool>>();
+            let nexts = (0..num_olmcs)
+                .map(|olmc_num| self.eval_next(&pins, olmc_num))
+                .collect::<Vec<bool>>();
+
+            let mut regs = self.regs.clone();
+            if let Some((last_clocks, last_nexts)) = &self.last {
+                for olmc_num in 0..num_olmcs {
+                    if clocks[olmc_num] && !last_clocks[olmc_num] {
+                        regs[olmc_num] = last_nexts[olmc_num];
+                    }
+                }
+            }
+            self.apply_async(&pins, &mut regs);
+            self.last = Some((clocks, nexts));
+
+            if regs == self.regs {
+                return Ok(self.pin_levels_driven(inputs, &outputs));
+            }
+            self.regs = regs;
+        }
+
+        Err(ErrorCode::Unstable)
+    }
+
+    // Apply a test vector, and check the outputs against it. Clock
+    // pulses are applied by stepping with the clock low, high and
+    // then low again, with the outputs checked at the end.
+    fn check_vector(&mut self, values: &str) -> Result<(), ErrorCode> {
+        let drive = |clock: bool| {
+            values
+                .chars()
+                .map(|c| c == '1' || (clock && c == 'C'))
+                .collect::<Vec<bool>>()
+        };
+
+        let mut levels = self.step(&drive(false))?;
+        if values.contains('C') {
+            self.step(&drive(true))?;
+            levels = self.step(&drive(false))?;
+        }
+
+        for (pin, (c, level)) in (1..).zip(values.chars().zip(levels)) {
+            let expected = match c {
+                'H' => Level::High,
+                'L' => Level::Low,
+                'Z' => Level::HighZ,
+                _ => continue,
+            };
+            if level != expected {
+                return Err(ErrorCode::VectorFailed {
+                    pin,
+                    expected: c,
+                    seen: level.to_char(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Let the combinatorial logic settle, giving the level driven by
+    // each OLMC.
+    fn settle(&self, inputs: &[bool]) -> Result<Vec<Level>, ErrorCode> {
+        let chip = self.gal.chip;
+        let num_pins = chip.num_pins();
+        assert_eq!(inputs.len(), num_pins, "need one input per pin");
+
+        let mut outputs = vec![Level::HighZ; chip.num_olmcs()];
+        for _ in 0..MAX_PASSES {
+            let pins = self.pin_levels(inputs, &outputs);
+            let new_outputs = (0..chip.num_olmcs())
+                .map(|olmc_num| self.eval_olmc(&pins, olmc_num))
+                .collect::<Vec<Level>>();
+            if new_outputs == outputs {
+                return Ok(outputs);
+            }
+            outputs = new_outputs;
+        }
+
+        Err(ErrorCode::Unstable)
+    }
+
+    // Combine the external inputs and the levels driven by the OLMCs
+    // to give the logic level on each pin.
+    fn pin_levels(&self, inputs: &[bool], outputs: &[Level]) -> Vec<bool> {
+        let chip = self.gal.chip;
+        (1..=chip.num_pins())
+            .map(|pin| match chip.pin_to_olmc(pin).map(|i| outputs[i]) {
+                Some(Level::High) => true,
+                Some(Level::Low) => false,
+                _ => inputs[pin - 1],
+            })
+            .collect()
+    }
+
+    // As 'pin_levels', but only showing input values on non-OLMC pins,
+    // so that undriven outputs show as HighZ.
+    fn pin_levels_driven(&self, inputs: &[bool], outputs: &[Level]) -> Vec<Level> {
+        let chip = self.gal.chip;
+        let num_pins = chip.num_pins();
+        (1..=num_pins)
+            .map(|pin| match chip.pin_to_olmc(pin) {
+                Some(olmc_num) => outputs[olmc_num],
+                None if pin == chip.vcc_pin() => Level::High,
+                None if pin == chip.gnd_pin() => Level::Low,
+                None => Level::from_bool(inputs[pin - 1]),
+            })
+            .collect()
+    }
+
+    // Work out the level an OLMC drives onto its pin.
+    fn eval_olmc(&self, pins: &[bool], olmc_num: usize) -> Level {
+        let gal = self.gal;
+        let chip = gal.chip;
+        let (xor, _) = self.mode_bits(olmc_num);
+        let start_row = chip.get_bounds(olmc_num).start_row;
+        let num_rows = chip.num_rows_for_olmc(olmc_num);
+
+        // Rows are split into the enable term, any other control
+        // terms, and the main sum.
+        let (enable, sum_start) = match self.output_mode(olmc_num) {
+            OutputMode::Input => return Level::HighZ,
+            OutputMode::Combinatorial => match chip {
+                Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B
+                    if gal.get_mode() == Mode::Simple =>
+                {
+                    (true, start_row)
+                }
+                Chip::GAL20RA10 => (self.eval_row(pins, start_row) && !pins[12], start_row + 4),
+                _ => (self.eval_row(pins, start_row), start_row + 1),
+            },
+            OutputMode::Registered => {
+                let enable = match chip {
+                    // Registered outputs are enabled by the /OE pin.
+                    Chip::GAL16V8 | Chip::ATF16V8B => !pins[10],
+                    Chip::GAL20V8 | Chip::ATF20V8B => !pins[12],
+                    Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+                        self.eval_row(pins, start_row)
+                    }
+                    Chip::GAL20RA10 => self.eval_row(pins, start_row) && !pins[12],
+                };
+                return if enable {
+                    self.reg_output(olmc_num)
+                } else {
+                    Level::HighZ
+                };
+            }
+        };
+
+        if !enable {
+            return Level::HighZ;
+        }
+
+        let sum = (sum_start..start_row + num_rows).any(|row| self.eval_row(pins, row));
+        // The XOR bit being set makes the output active high.
+        Level::from_bool(sum == xor)
+    }
+
+    // The level a registered output drives onto its pin.
+    fn reg_output(&self, olmc_num: usize) -> Level {
+        let q = self.regs[olmc_num];
+        match self.gal.chip {
+            // The XOR is ahead of the register, and the output is /Q.
+            Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => Level::from_bool(!q),
+            // S0 selects Q or /Q.
+            _ => {
+                let (s0, _) = self.mode_bits(olmc_num);
+                Level::from_bool(q == s0)
+            }
+        }
+    }
+
+    // The clock signal for an OLMC's register.
+    fn eval_clock(&self, pins: &[bool], olmc_num: usize) -> bool {
+        let chip = self.gal.chip;
+        match chip {
+            // Each OLMC has its own clock term.
+            Chip::GAL20RA10 => self.eval_row(pins, chip.get_bounds(olmc_num).start_row + 1),
+            // Otherwise, it's pin 1.
+            _ => pins[0],
+        }
+    }
+
+    // The value the OLMC's register would load on a clock edge.
+    fn eval_next(&self, pins: &[bool], olmc_num: usize) -> bool {
+        let gal = self.gal;
+        let chip = gal.chip;
+        let (xor, _) = self.mode_bits(olmc_num);
+        let start_row = chip.get_bounds(olmc_num).start_row;
+        let end_row = start_row + chip.num_rows_for_olmc(olmc_num);
+        let sum = |first_row| (first_row..end_row).any(|row| self.eval_row(pins, row));
+
+        match chip {
+            Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => {
+                sum(start_row) != xor
+            }
+            // SP is a synchronous preset, shared by all OLMCs.
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+                self.eval_row(pins, chip.sp_row()) || sum(start_row + 1)
+            }
+            Chip::GAL20RA10 => sum(start_row + 4),
+        }
+    }
+
+    // Apply the asynchronous resets and presets to the registers.
+    fn apply_async(&self, pins: &[bool], regs: &mut [bool]) {
+        let chip = self.gal.chip;
+        match chip {
+            // AR resets all the registers.
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12
+                if self.eval_row(pins, chip.ar_row()) =>
+            {
+                regs.iter_mut().for_each(|q| *q = false);
+            }
+            // Each OLMC has its own reset and preset terms.
+            Chip::GAL20RA10 => {
+                for (olmc_num, q) in regs.iter_mut().enumerate() {
+                    let start_row = chip.get_bounds(olmc_num).start_row;
+                    if self.eval_row(pins, start_row + 2) {
+                        *q = false;
+                    } else if self.eval_row(pins, start_row + 3) {
+                        *q = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Read the OLMC's configuration from the mode bits.
+    fn output_mode(&self, olmc_num: usize) -> OutputMode {
+        let gal = self.gal;
+        let chip = gal.chip;
+        let (_, ac1) = self.mode_bits(olmc_num);
+
+        match chip {
+            Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B => {
+                match (gal.get_mode(), ac1) {
+                    (Mode::Simple, false) => OutputMode::Combinatorial,
+                    (Mode::Simple, true) => OutputMode::Input,
+                    (Mode::Complex, true) => OutputMode::Combinatorial,
+                    (Mode::Complex, false) => OutputMode::Input,
+                    (Mode::Registered, true) => OutputMode::Combinatorial,
+                    (Mode::Registered, false) => OutputMode::Registered,
+                }
+            }
+            // S1 is stored in 'ac1'.
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+                if ac1 {
+                    OutputMode::Combinatorial
+                } else {
+                    OutputMode::Registered
+                }
+            }
+            // The register is bypassed when ARST and APRST are both
+            // true. As these are product terms, that means both rows
+            // have no fuses connected.
+            Chip::GAL20RA10 => {
+                let start_row = chip.get_bounds(olmc_num).start_row;
+                if self.row_is_true(start_row + 2) && self.row_is_true(start_row + 3) {
+                    OutputMode::Combinatorial
+                } else {
+                    OutputMode::Registered
+                }
+            }
+        }
+    }
+
+    // The XOR (or S0) and AC1 (or S1) bits are stored in reverse order
+    // to the OLMCs.
+    fn mode_bits(&self, olmc_num: usize) -> (bool, bool) {
+        let idx = self.gal.chip.num_olmcs() - 1 - olmc_num;
+        (self.gal.xor[idx], self.gal.ac1[idx])
+    }
+
+    fn row_is_true(&self, row: usize) -> bool {
+        let row_len = self.gal.chip.num_cols();
+        self.gal.fuses[row * row_len..(row + 1) * row_len]
+            .iter()
+            .all(|fuse| *fuse)
+    }
+
+    // Evaluate a single product term. An intact (false) fuse connects
+    // the column to the AND gate.
+    fn eval_row(&self, pins: &[bool], row: usize) -> bool {
+        let gal = self.gal;
+        let chip = gal.chip;
+        let row_len = chip.num_cols();
+
+        // Disabled product terms are always false.
+        if (chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8) && !gal.pt[row] {
+            return false;
+        }
+
+        gal.fuses[row * row_len..(row + 1) * row_len]
+            .iter()
+            .enumerate()
+            .filter(|(_, fuse)| !**fuse)
+            .all(|(col, _)| self.eval_column(pins, col))
+    }
+
+    // Find the value on a column of the AND array. Even columns carry
+    // the true value of their input, odd ones the complement.
+    fn eval_column(&self, pins: &[bool], col: usize) -> bool {
+        let value = match self.gal.column_to_pin(col) {
+            Some(pin) => self.feedback(pins, pin),
+            // Unconnected columns float high.
+            None => true,
+        };
+        value != (col % 2 == 1)
+    }
+
+    // The value fed into the array for a pin. Registered outputs on
+    // the GALxxV8s and GAL22V10 feed back /Q, whether or not the
+    // output is enabled. On the GAL22V10 this is the opposite sense to
+    // an active-high output, which is why 'GAL::needs_flip' exists.
+    fn feedback(&self, pins: &[bool], pin: usize) -> bool {
+        let chip = self.gal.chip;
+        match chip.pin_to_olmc(pin) {
+            Some(olmc_num)
+                if chip.arch() != Chip::GAL20RA10
+                    && self.output_mode(olmc_num) == OutputMode::Registered =>
+            {
+                !self.regs[olmc_num]
+            }
+            _ => pins[pin - 1],
+        }
+    }
+}
+
+// Run the test vectors through a freshly powered-up GAL, stopping at
+// the first one that fails.
+pub fn check_vectors(gal: &GAL, vectors: &[Vector]) -> Result<(), Error> {
+    let mut sim = Simulator::new(gal);
+    for vector in vectors.iter() {
+        at_line(vector.line_num, sim.check_vector(&vector.values))?;
+    }
+    Ok(())
+}
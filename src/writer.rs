@@ -14,9 +14,12 @@ use std::{
 };
 
 use crate::{
-    blueprint::OLMC,
+    blueprint::{Blueprint, PinMode, OLMC},
     chips::Chip,
+    errors::LineNum,
     gal::{Mode, GAL},
+    parser::Vector,
+    sim::Simulator,
 };
 
 #[derive(Debug)]
@@ -24,7 +27,31 @@ pub struct Config {
     pub gen_fuse: bool,
     pub gen_chip: bool,
     pub gen_pin: bool,
+    pub gen_tt: bool,
     pub jedec_sec_bit: bool,
+    pub minimise: bool,
+    pub auto_polarity: bool,
+    pub jedec_vectors: bool,
+    pub pt_disable: bool,
+    pub split: bool,
+}
+
+// The defaults match galette's command line with no options.
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            gen_fuse: true,
+            gen_chip: true,
+            gen_pin: true,
+            gen_tt: false,
+            jedec_sec_bit: false,
+            minimise: true,
+            auto_polarity: false,
+            jedec_vectors: false,
+            pt_disable: false,
+            split: false,
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////
@@ -40,24 +67,41 @@ fn write_file(base: &Path, ext: &str, buf: &str) -> Result<(), Error> {
 pub fn write_files(
     file_name: &str,
     config: &Config,
-    pin_names: &[String],
-    olmcs: &[OLMC],
+    blueprint: &Blueprint,
     gal: &GAL,
 ) -> Result<(), Error> {
     let base = PathBuf::from(file_name);
-
-    write_file(&base, "jed", &make_jedec(config, gal))?;
+    let pin_names = &blueprint.pins;
+    let olmcs = &blueprint.olmcs;
+    let vectors = &blueprint.vectors;
+    // Only echo the signature if it was set explicitly, so the output
+    // matches galasm's otherwise.
+    let sig = blueprint
+        .directives
+        .sig
+        .is_some()
+        .then_some(blueprint.sig.as_slice());
+
+    write_file(&base, "jed", &make_jedec(config, gal, vectors))?;
 
     if config.gen_fuse {
         write_file(&base, "fus", &make_fuse(pin_names, gal))?;
     }
 
     if config.gen_pin {
-        write_file(&base, "pin", &make_pin(gal, pin_names, olmcs))?;
+        write_file(
+            &base,
+            "pin",
+            &make_pin(gal, pin_names, olmcs, sig, &blueprint.range_rows()),
+        )?;
     }
 
     if config.gen_chip {
-        write_file(&base, "chp", &make_chip(gal.chip, pin_names))?;
+        write_file(&base, "chp", &make_chip(gal.chip, pin_names, sig))?;
+    }
+
+    if config.gen_tt {
+        write_file(&base, "tt", &make_tt(gal, pin_names, olmcs))?;
     }
 
     Ok(())
@@ -68,14 +112,14 @@ pub fn write_files(
 //
 
 // Structure to track the JEDEC fuse checksum.
-struct CheckSummer {
+pub(crate) struct CheckSummer {
     bit_num: u8,
     byte: u8,
     sum: u16,
 }
 
 impl CheckSummer {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         CheckSummer {
             bit_num: 0,
             byte: 0,
@@ -83,7 +127,7 @@ impl CheckSummer {
         }
     }
 
-    fn add(&mut self, bit: bool) {
+    pub(crate) fn add(&mut self, bit: bool) {
         if bit {
             self.byte |= 1 << self.bit_num
         };
@@ -96,7 +140,7 @@ impl CheckSummer {
         }
     }
 
-    fn get(&self) -> u16 {
+    pub(crate) fn get(&self) -> u16 {
         self.sum + self.byte as u16
     }
 }
@@ -110,7 +154,7 @@ struct FuseBuilder<'a> {
 }
 
 impl<'a> FuseBuilder<'a> {
-    fn new(buf: &mut String) -> FuseBuilder {
+    fn new(buf: &mut String) -> FuseBuilder<'_> {
         FuseBuilder {
             buf,
             checksum: CheckSummer::new(),
@@ -154,8 +198,8 @@ impl<'a> FuseBuilder<'a> {
 // Core function to generate a string of the JEDEC file, given the
 // config, fuses, etc.
 //
-// It's galasm-compatible.
-pub fn make_jedec(config: &Config, gal: &GAL) -> String {
+// It's galasm-compatible, unless test vectors are written.
+pub fn make_jedec(config: &Config, gal: &GAL, vectors: &[Vector]) -> String {
     let chip = gal.chip;
     let row_len = chip.num_cols();
 
@@ -176,7 +220,14 @@ pub fn make_jedec(config: &Config, gal: &GAL) -> String {
     });
 
     // Number of fuses.
-    let _ = writeln!(buf, "*QF{}", chip.total_size());
+    let _ = writeln!(buf, "*QF{}", gal.total_size());
+
+    let write_vectors = config.jedec_vectors && !vectors.is_empty();
+    if write_vectors {
+        // Number of pins and test vectors.
+        let _ = writeln!(buf, "*QP{}", chip.num_pins());
+        let _ = writeln!(buf, "*QV{}", vectors.len());
+    }
 
     {
         // Construct fuse matrix.
@@ -195,18 +246,21 @@ pub fn make_jedec(config: &Config, gal: &GAL) -> String {
             }
         }
 
-        // XOR bits are interleaved with S1 bits on GAL22V10 (stored
-        // in the 'ac1' field, as it's the same function).
-        if chip != Chip::GAL22V10 {
+        // XOR bits are interleaved with S1 bits on GAL22V10, GAL18V10
+        // and GAL26CV12 (stored in the 'ac1' field, as it's the same
+        // function).
+        if !chip.has_arsp() {
             fuse_builder.add(&gal.xor)
         } else {
             let bits = itertools::interleave(gal.xor.iter(), gal.ac1.iter());
             fuse_builder.add_iter(bits);
         }
 
-        fuse_builder.add(&gal.sig);
+        if !gal.sig.is_empty() {
+            fuse_builder.add(&gal.sig);
+        }
 
-        if (chip == Chip::GAL16V8) || (chip == Chip::GAL20V8) {
+        if (chip.arch() == Chip::GAL16V8) || (chip.arch() == Chip::GAL20V8) {
             fuse_builder.add(&gal.ac1);
             fuse_builder.add(&gal.pt);
             fuse_builder.add(&[gal.syn]);
@@ -217,6 +271,12 @@ pub fn make_jedec(config: &Config, gal: &GAL) -> String {
         fuse_builder.checksum();
     }
 
+    if write_vectors {
+        for (i, vector) in vectors.iter().enumerate() {
+            let _ = writeln!(buf, "*V{:04} {}", i + 1, vector.values);
+        }
+    }
+
     buf.push_str("*\n");
     buf.push('\x03');
 
@@ -226,7 +286,7 @@ pub fn make_jedec(config: &Config, gal: &GAL) -> String {
     buf
 }
 
-fn file_checksum(data: &[u8]) -> u16 {
+pub(crate) fn file_checksum(data: &[u8]) -> u16 {
     data.iter().fold(0, |checksum: u16, byte| {
         checksum.wrapping_add(u16::from(*byte))
     })
@@ -236,7 +296,7 @@ fn file_checksum(data: &[u8]) -> u16 {
 // 'make_chip' draws out the chip with pin assignments.
 //
 
-fn make_chip(chip: Chip, pin_names: &[String]) -> String {
+fn make_chip(chip: Chip, pin_names: &[String], sig: Option<&[u8]>) -> String {
     let num_of_pins = pin_names.len();
     let mut buf = String::new();
 
@@ -263,44 +323,88 @@ fn make_chip(chip: Chip, pin_names: &[String]) -> String {
 
     let _ = writeln!(buf, "\n{:25} -------------------", "");
 
+    if let Some(sig) = sig {
+        let _ = writeln!(buf, "\nSignature: {}", sig_to_string(sig));
+    }
+
     buf
 }
 
+// The signature as hex, followed by the text, with trailing zero bytes
+// dropped and unprintable characters shown as '.'.
+fn sig_to_string(sig: &[u8]) -> String {
+    let mut bytes = [0u8; 8];
+    bytes[..sig.len()].copy_from_slice(sig);
+    let text = sig
+        .iter()
+        .rev()
+        .skip_while(|c| **c == 0)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .map(|c| {
+            if c.is_ascii_graphic() || *c == b' ' {
+                *c as char
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>();
+    format!("0x{:016X} \"{}\"", u64::from_be_bytes(bytes), text)
+}
+
 ////////////////////////////////////////////////////////////////////////
 // 'make_pin' lists the pin assignments.
 //
 
 fn pin_type(gal: &GAL, olmcs: &[OLMC], i: usize) -> &'static str {
     let chip = gal.chip;
-    let num_pins = chip.num_pins();
 
     if let Some(olmc) = chip.pin_to_olmc(i) {
         let olmc = &olmcs[olmc];
         if olmc.output.is_some() {
-            "Output"
+            if olmc.inverted {
+                "Output (inverted to fit)"
+            } else {
+                "Output"
+            }
         } else if !olmc.feedback {
             "NC"
         } else {
             "Input"
         }
-    } else if i == num_pins / 2 {
+    } else if i == chip.gnd_pin() {
         "GND"
-    } else if i == num_pins {
+    } else if i == chip.vcc_pin() {
         "VCC"
     } else {
         match chip {
-            Chip::GAL16V8 | Chip::GAL20V8 if gal.get_mode() == Mode::Registered && i == 1 => {
+            Chip::GAL16V8 | Chip::ATF16V8B | Chip::GAL20V8 | Chip::ATF20V8B
+                if gal.get_mode() == Mode::Registered && i == 1 =>
+            {
                 "Clock"
             }
-            Chip::GAL16V8 if gal.get_mode() == Mode::Registered && i == 11 => "/OE",
-            Chip::GAL20V8 if gal.get_mode() == Mode::Registered && i == 13 => "/OE",
-            Chip::GAL22V10 if i == 1 => "Clock/Input",
+            Chip::GAL16V8 | Chip::ATF16V8B if gal.get_mode() == Mode::Registered && i == 11 => {
+                "/OE"
+            }
+            Chip::GAL20V8 | Chip::ATF20V8B if gal.get_mode() == Mode::Registered && i == 13 => {
+                "/OE"
+            }
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 if i == 1 => {
+                "Clock/Input"
+            }
             _ => "Input",
         }
     }
 }
 
-fn make_pin(gal: &GAL, pin_names: &[String], olmcs: &[OLMC]) -> String {
+fn make_pin(
+    gal: &GAL,
+    pin_names: &[String],
+    olmcs: &[OLMC],
+    sig: Option<&[u8]>,
+    range_rows: &[(LineNum, usize)],
+) -> String {
     let mut buf = String::new();
     buf.push_str("\n\n");
     buf.push_str(" Pin # | Name     | Pin Type\n");
@@ -317,6 +421,23 @@ fn make_pin(gal: &GAL, pin_names: &[String], olmcs: &[OLMC]) -> String {
     }
     buf.push('\n');
 
+    if let Some(sig) = sig {
+        let _ = writeln!(buf, "Signature: {}\n", sig_to_string(sig));
+    }
+
+    for (line_num, count) in range_rows.iter() {
+        let _ = writeln!(
+            buf,
+            "Equation with range comparisons in line {} uses {} product term{}",
+            line_num,
+            count,
+            if *count == 1 { "" } else { "s" }
+        );
+    }
+    if !range_rows.is_empty() {
+        buf.push('\n');
+    }
+
     buf
 }
 
@@ -360,23 +481,26 @@ fn make_fuse(pin_names: &[String], gal: &GAL) -> String {
     let chip = gal.chip;
     let row_len = chip.num_cols();
 
-    let mut pin = chip.last_olmc();
     let mut row = 0;
 
-    // AR for the 22V10
-    if chip == Chip::GAL22V10 {
+    // AR for the 22V10, 18V10 and 26CV12
+    if chip.has_arsp() {
         buf.push_str("\n\nAR");
         make_row(&mut buf, &mut row, row_len, &gal.fuses);
     }
 
-    let last_olmc = chip.last_olmc();
-    for olmc in 0..chip.num_olmcs() {
-        let xor = to_bit(gal.xor[last_olmc - pin]);
-        let ac1 = to_bit(gal.ac1[last_olmc - pin]);
+    // The OLMCs appear in the fuse map from the last one down.
+    for olmc in (0..chip.num_olmcs()).rev() {
+        let pin = chip.olmc_to_pin(olmc);
+        let idx = chip.num_olmcs() - 1 - olmc;
+        let xor = to_bit(gal.xor[idx]);
+        let ac1 = to_bit(gal.ac1[idx]);
         let flags = match chip {
-            Chip::GAL16V8 => format!("XOR = {:>1}   AC1 = {:>1}", xor, ac1),
-            Chip::GAL20V8 => format!("XOR = {:>1}   AC1 = {:>1}", xor, ac1),
-            Chip::GAL22V10 => format!("S0 = {:>1}   S1 = {:>1}", xor, ac1),
+            Chip::GAL16V8 | Chip::ATF16V8B => format!("XOR = {:>1}   AC1 = {:>1}", xor, ac1),
+            Chip::GAL20V8 | Chip::ATF20V8B => format!("XOR = {:>1}   AC1 = {:>1}", xor, ac1),
+            Chip::GAL22V10 | Chip::ATF22V10C | Chip::GAL18V10 | Chip::GAL26CV12 => {
+                format!("S0 = {:>1}   S1 = {:>1}", xor, ac1)
+            }
             Chip::GAL20RA10 => format!("S0 = {:>1}", xor),
         };
         let _ = write!(
@@ -389,14 +513,16 @@ fn make_fuse(pin_names: &[String], gal: &GAL) -> String {
 
         for _ in 0..chip.num_rows_for_olmc(olmc) {
             // Print all fuses of an OLMC
+            let pt = row;
             make_row(&mut buf, &mut row, row_len, &gal.fuses);
+            if (chip.arch() == Chip::GAL16V8 || chip.arch() == Chip::GAL20V8) && !gal.pt[pt] {
+                buf.push_str("  PT disabled");
+            }
         }
-
-        pin -= 1;
     }
 
-    // SP for the 22V10
-    if chip == Chip::GAL22V10 {
+    // SP for the 22V10, 18V10 and 26CV12
+    if chip.has_arsp() {
         buf.push_str("\n\nSP");
         make_row(&mut buf, &mut row, row_len, &gal.fuses);
     }
@@ -405,6 +531,101 @@ fn make_fuse(pin_names: &[String], gal: &GAL) -> String {
     buf
 }
 
+////////////////////////////////////////////////////////////////////////
+// 'make_tt' writes out a truth table, by simulating the fuse map.
+//
+
+// Beyond this many inputs, the table gets silly.
+const MAX_TT_INPUTS: usize = 12;
+
+// The pins feeding the AND array, as seen in the fuse map, ignoring
+// cleared rows (which connect everything).
+fn array_inputs(gal: &GAL) -> Vec<usize> {
+    let row_len = gal.chip.num_cols();
+    let mut pins = gal
+        .fuses
+        .chunks(row_len)
+        .filter(|row| row.iter().any(|fuse| *fuse))
+        .flat_map(|row| (0..row_len).filter(move |col| !row[*col]))
+        .filter_map(|col| gal.column_to_pin(col))
+        .collect::<Vec<usize>>();
+    pins.sort_unstable();
+    pins.dedup();
+    pins
+}
+
+fn make_tt(gal: &GAL, pin_names: &[String], olmcs: &[OLMC]) -> String {
+    let chip = gal.chip;
+    let mut buf = String::new();
+    buf.push_str("\n\n");
+
+    // Only combinatorial outputs are listed. Registered outputs fed
+    // back into the array are held in their power-up state.
+    let is_output = |pin: usize| {
+        chip.pin_to_olmc(pin)
+            .is_some_and(|olmc| olmcs[olmc].output.is_some())
+    };
+    let inputs = array_inputs(gal)
+        .into_iter()
+        .filter(|pin| !is_output(*pin))
+        .collect::<Vec<usize>>();
+    let outputs = (1..=chip.num_pins())
+        .filter(|pin| {
+            chip.pin_to_olmc(*pin).is_some_and(|olmc| {
+                matches!(
+                    olmcs[olmc].output,
+                    Some((PinMode::Combinatorial, _)) | Some((PinMode::Tristate, _))
+                )
+            })
+        })
+        .collect::<Vec<usize>>();
+
+    if outputs.is_empty() {
+        buf.push_str("No combinatorial outputs\n");
+        return buf;
+    }
+    if inputs.len() > MAX_TT_INPUTS {
+        let _ = writeln!(
+            buf,
+            "Too many inputs for a truth table (max: {}, saw: {})",
+            MAX_TT_INPUTS,
+            inputs.len()
+        );
+        return buf;
+    }
+
+    let width = |pin: usize| pin_names[pin - 1].len();
+    let header = |pins: &[usize]| pins.iter().map(|pin| pin_names[pin - 1].as_str()).join(" ");
+    let rule = |pins: &[usize]| pins.iter().map(|pin| "-".repeat(width(*pin))).join("-");
+    let _ = writeln!(buf, "{} | {}", header(&inputs), header(&outputs));
+    let _ = writeln!(buf, "{}-+-{}", rule(&inputs), rule(&outputs));
+
+    // The first input is the most significant. Unused pins, and
+    // enables such as /OE, are held low.
+    let sim = Simulator::new(gal);
+    for bits in 0..1usize << inputs.len() {
+        let mut values = vec![false; chip.num_pins()];
+        for (i, pin) in inputs.iter().rev().enumerate() {
+            values[pin - 1] = bits & (1 << i) != 0;
+        }
+
+        let ins = inputs
+            .iter()
+            .map(|pin| format!("{:<w$}", to_bit(values[pin - 1]), w = width(*pin)))
+            .join(" ");
+        let outs = match sim.eval(&values) {
+            Ok(levels) => outputs
+                .iter()
+                .map(|pin| format!("{:<w$}", levels[pin - 1].to_char(), w = width(*pin)))
+                .join(" "),
+            Err(e) => e.to_string(),
+        };
+        let _ = writeln!(buf, "{}", format!("{} | {}", ins, outs).trim_end());
+    }
+
+    buf
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
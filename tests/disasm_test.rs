@@ -0,0 +1,89 @@
+//
+// disasm_test.rs: Check the disassembler against the assembler.
+//
+// Every JEDEC file in the test cases should disassemble into a .pld
+// file that assembles back into exactly the same JEDEC file.
+//
+
+use std::fs::{self, create_dir_all, remove_dir_all};
+use std::path::Path;
+
+use anyhow::Result;
+use test_bin::get_test_bin;
+
+use galette::writer;
+
+const TEMP_DIR: &str = "test_temp_disasm";
+
+fn get_jeds(dir: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() == Some("jed".as_ref()) {
+            names.push(path.to_str().unwrap().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[test]
+fn test_disasm_round_trip() -> Result<()> {
+    if Path::new(TEMP_DIR).exists() {
+        remove_dir_all(TEMP_DIR)?;
+    }
+    create_dir_all(TEMP_DIR)?;
+
+    // Don't minimise, so that we get back exactly what was
+    // disassembled.
+    let config = writer::Config {
+        gen_fuse: false,
+        gen_chip: false,
+        gen_pin: false,
+        minimise: false,
+        ..Default::default()
+    };
+
+    for dir in [
+        "testcases/success",
+        "testcases/nominimise",
+        "testcases/autopolarity",
+        "testcases/split",
+    ] {
+        for name in get_jeds(dir)? {
+            let pld = galette::disassemble(&name).unwrap_or_else(|e| panic!("{}", e));
+
+            let base = Path::new(TEMP_DIR).join(Path::new(&name).file_stem().unwrap());
+            let pld_name = base.with_extension("pld");
+            fs::write(&pld_name, &pld)?;
+            galette::assemble(pld_name.to_str().unwrap(), &config)
+                .unwrap_or_else(|e| panic!("'{}' disassembled to:\n{}\n{}", name, pld, e));
+
+            assert_eq!(
+                fs::read_to_string(base.with_extension("jed"))?,
+                fs::read_to_string(&name)?,
+                "'{}' did not round-trip, disassembled to:\n{}",
+                name,
+                pld
+            );
+        }
+    }
+
+    remove_dir_all(TEMP_DIR)?;
+    Ok(())
+}
+
+#[test]
+fn test_disasm_command() -> Result<()> {
+    let results = get_test_bin("galette")
+        .args(["disasm", "testcases/success/GAL22V10_reg.jed"])
+        .output()?;
+    assert!(results.status.success());
+    assert!(results.stderr.is_empty());
+
+    let pld = std::str::from_utf8(&results.stdout)?;
+    assert!(pld.starts_with("GAL22V10\n"));
+    assert!(pld.contains(".R = "));
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//
+// fitter_test.rs: Check the fitter leaves alone what it must.
+//
+// Most of the fitter is covered by the regression tests, but a pass
+// declining to do something shows up there as a failure to fit, so
+// check the decision itself here.
+//
+
+use galette::{blueprint::Blueprint, fitter, parser};
+
+#[test]
+fn test_polarity_registered() {
+    // The complement would fit, but on the GAL22V10 the polarity
+    // comes after the flip-flop, so inverting it would change what
+    // AR, SP and power-up reset put on the pin.
+    let content = parser::parse("testcases/failure/autopolarity_reg.pld").unwrap();
+    let mut blueprint = Blueprint::from(&content).unwrap();
+    let before = blueprint.olmcs[9].output.clone();
+    fitter::choose_polarity(&mut blueprint, true);
+
+    let olmc = &blueprint.olmcs[9];
+    assert!(!olmc.inverted);
+    assert_eq!(olmc.output, before);
+}
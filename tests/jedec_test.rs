@@ -0,0 +1,162 @@
+//
+// jedec_test.rs: Check the JEDEC reader against the writer.
+//
+// Every JEDEC file in the test cases should read back in and, when
+// written out again, produce exactly the same file.
+//
+
+use std::fs;
+
+use anyhow::Result;
+
+use galette::{chips::Chip, errors::ErrorCode, jedec, pal::PAL, writer};
+
+fn get_jeds(dir: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() == Some("jed".as_ref()) {
+            names.push(path.to_str().unwrap().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[test]
+fn test_jedec_round_trip() -> Result<()> {
+    for dir in [
+        "testcases/success",
+        "testcases/security",
+        "testcases/nominimise",
+        "testcases/autopolarity",
+        "testcases/ptdisable",
+        "testcases/split",
+    ] {
+        for name in get_jeds(dir)? {
+            let original = fs::read_to_string(&name)?;
+            let jedec = jedec::parse(&name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+            let config = writer::Config {
+                jedec_sec_bit: jedec.sec_bit,
+                ..Default::default()
+            };
+            assert_eq!(
+                writer::make_jedec(&config, &jedec.gal, &[]),
+                original,
+                "'{}' did not round-trip",
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_jedec_checksums() -> Result<()> {
+    let original = fs::read_to_string("testcases/success/GAL16V8_combinatorial.jed")?;
+
+    // Changing a fuse breaks the fuse checksum.
+    let bad_fuse = original.replacen("*L0768 0", "*L0768 1", 1);
+    assert!(matches!(
+        jedec::parse_str(&bad_fuse).err().unwrap().code,
+        ErrorCode::BadFuseChecksum { .. }
+    ));
+
+    // Changing the header only breaks the file checksum.
+    let bad_file = original.replacen("GAL-Assembler", "GAL-assembler", 1);
+    assert!(matches!(
+        jedec::parse_str(&bad_file).err().unwrap().code,
+        ErrorCode::BadFileChecksum { .. }
+    ));
+
+    // Unless it's the dummy checksum.
+    let (body, _) = bad_file.split_at(bad_file.find('\x03').unwrap() + 1);
+    assert!(jedec::parse_str(&format!("{}0000\n", body)).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_jedec_fuse_count() -> Result<()> {
+    let original = fs::read_to_string("testcases/success/GAL16V8_combinatorial.jed")?;
+
+    // A huge fuse count is rejected before anything is allocated.
+    let huge = original.replacen("*QF2194", "*QF999999999999", 1);
+    assert!(matches!(
+        jedec::parse_str(&huge).err().unwrap().code,
+        ErrorCode::BadFuseCount {
+            count: 999999999999
+        }
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_jedec_no_ues() -> Result<()> {
+    // The 5828-fuse map is the 5892-fuse one without the UES.
+    let with_ues = jedec::parse("testcases/success/ATF22V10C.jed")?.gal;
+    let without = jedec::parse("testcases/success/ATF22V10C_noues.jed")?.gal;
+    assert_eq!(without.chip, Chip::ATF22V10C);
+    assert!(without.sig.is_empty());
+    assert_eq!(without.fuses, with_ues.fuses);
+    assert_eq!(without.xor, with_ues.xor);
+    assert_eq!(without.ac1, with_ues.ac1);
+
+    // Without the header naming the part, it could be a PAL22V10, so
+    // the fuse count alone isn't enough.
+    let original = fs::read_to_string("testcases/success/ATF22V10C_noues.jed")?;
+    let unnamed = original.replacen("Device:         ATF22V10C\n", "", 1);
+    let (body, _) = unnamed.split_at(unnamed.find('\x03').unwrap() + 1);
+    assert!(matches!(
+        jedec::parse_str(&format!("{}0000\n", body))
+            .err()
+            .unwrap()
+            .code,
+        ErrorCode::BadFuseCount { count: 5828 }
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_pal_conversion() -> Result<()> {
+    // The PAL fuse maps were read out of the GALs assembled with
+    // "%TARGET", so converting them should give back the same GALs,
+    // apart from the signature.
+    for pal in [PAL::PAL16R4, PAL::PAL20L8] {
+        let name = pal.name();
+        let assembled = jedec::parse(&format!("testcases/success/{}.jed", name))?.gal;
+        let converted = jedec::parse_pal(&format!("testcases/pal2gal/{}.jed", name), pal)?.gal;
+
+        assert_eq!(converted.fuses, assembled.fuses, "{}", name);
+        assert_eq!(converted.xor, assembled.xor, "{}", name);
+        assert_eq!(converted.get_mode(), assembled.get_mode(), "{}", name);
+        // galasm leaves outputs with no equation as combinatorial,
+        // rather than tristate, so only compare AC1 for outputs with
+        // a programmed row.
+        let chip = pal.chip();
+        let num_olmcs = chip.num_olmcs();
+        for olmc in 0..num_olmcs {
+            let bounds = chip.get_bounds(olmc);
+            let rows = bounds.start_row..bounds.start_row + bounds.max_row;
+            let fuses = &converted.fuses[rows.start * chip.num_cols()..rows.end * chip.num_cols()];
+            if fuses.iter().any(|fuse| *fuse) {
+                let i = num_olmcs - 1 - olmc;
+                assert_eq!(converted.ac1[i], assembled.ac1[i], "{} OLMC {}", name, olmc);
+            }
+        }
+    }
+
+    // The fuse count must match the PAL.
+    assert!(matches!(
+        jedec::parse_pal("testcases/pal2gal/PAL16R4.jed", PAL::PAL20L8)
+            .err()
+            .unwrap()
+            .code,
+        ErrorCode::PALFuseCount { .. }
+    ));
+
+    Ok(())
+}
@@ -71,7 +71,7 @@ fn check_invocation_failed(name: &str, messages: &HashMap<&str, &str>, res: std:
             name,
             *messages
                 .get(name)
-                .expect(&format!("No known error message for '{}'", name))
+                .unwrap_or_else(|| panic!("No known error message for '{}'", name))
         ),
         "'{:?}' produced unexpected output to stderr",
         name
@@ -133,12 +133,72 @@ fn test_security_bit() -> Result<()> {
     Ok(())
 }
 
-const FAILURE_MESSAGES: [(&str, &str); 82] = [
+// Run galette with a single flag on the testcase of the same name as
+// its directory, and compare against the expected output.
+fn check_flag(dir: &str, flag: &str) -> Result<()> {
+    let temp_dir = format!("test_temp_{}", dir);
+    let file_name = format!("{}.pld", dir);
+    ensure_dir_exists(&temp_dir)?;
+
+    std::fs::copy(
+        format!("testcases/{}/{}", dir, file_name),
+        format!("{}/{}", temp_dir, file_name),
+    )?;
+
+    let results = get_test_bin("galette")
+        .current_dir(&temp_dir)
+        .args([flag, &file_name])
+        .output()?;
+    check_invocation_succeeded(&file_name, results);
+
+    check_output_matches(&format!("testcases/{}", dir), &temp_dir)?;
+
+    remove_dir_all(&temp_dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_flags() -> Result<()> {
+    for (dir, flag) in [
+        ("nominimise", "-m"),
+        ("autopolarity", "-a"),
+        ("split", "-x"),
+        ("truthtable", "-t"),
+        ("vectors", "-v"),
+        ("ptdisable", "-d"),
+    ] {
+        check_flag(dir, flag)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_pal2gal() -> Result<()> {
+    for pal in ["PAL16R4", "PAL20L8"] {
+        let results = get_test_bin("galette")
+            .current_dir("testcases/pal2gal")
+            .args(["pal2gal", pal, &format!("{}.jed", pal)])
+            .output()?;
+        assert!(results.status.success(), "'{}' did not succeed", pal);
+        assert!(results.stderr.is_empty());
+        assert_eq!(
+            std::str::from_utf8(&results.stdout)?,
+            fs::read_to_string(format!("testcases/pal2gal/{}_gal.jed", pal))?,
+            "'{}' converted differently",
+            pal
+        );
+    }
+
+    Ok(())
+}
+
+const FAILURE_MESSAGES: [(&str, &str); 127] = [
     ("GAL16V8_badname.pld", "Error in line 1: type of GAL expected\n"),
     ("GAL16V8_complex_12.pld", "Error in line 9: pin 12 can't be used as input in complex mode\n"),
     ("GAL16V8_complex_19.pld", "Error in line 9: pin 19 can't be used as input in complex mode\n"),
     ("GAL16V8_reg_1.pld", "Error in line 7: pin 1 is reserved for 'Clock' in registered mode\n"),
     ("GAL16V8_reg_11.pld", "Error in line 7: pin 11 is reserved for '/OE' in registered mode\n"),
+    ("GAL18V10_toomany.pld", "Error in line 7: too many product terms in sum for pin (max: 8, saw: 9)\n"),
     ("GAL20RA10_badname.pld", "Error in line 1: type of GAL expected\n"),
     ("GAL20RA10_pin1.pld", "Error in line 7: pin 1 is reserved for '/PL' on GAL20RA10 devices and can't be used in equations\n"),
     ("GAL20RA10_pin13.pld", "Error in line 7: pin 13 is reserved for '/OE' on GAL20RA10 devices and can't be used in equations\n"),
@@ -149,7 +209,9 @@ const FAILURE_MESSAGES: [(&str, &str); 82] = [
     ("GAL20V8_reg_1.pld", "Error in line 7: pin 1 is reserved for 'Clock' in registered mode\n"),
     ("GAL20V8_reg_13.pld", "Error in line 7: pin 13 is reserved for '/OE' in registered mode\n"),
     ("GAL22V10_badname.pld", "Error in line 1: type of GAL expected\n"),
+    ("GAL26CV12_vcc.pld", "Error in line 4: pin 7 must be named VCC\n"),
     ("arbad.pld", "Error in line 5: GAL22V10: AR is not allowed as pinname\n"),
+    ("autopolarity_reg.pld", "Error in line 9: too many product terms in sum for pin (max: 8, saw: 9)\n"),
     ("badarext.pld", "Error in line 23: no suffix is allowed for AR\n"),
     ("badarusage.pld", "Error in line 21: use of AR is not allowed in equations\n"),
     ("badclk.pld", "Error in line 7: .CLK is not allowed when this type of GAL is used\n"),
@@ -161,11 +223,31 @@ const FAILURE_MESSAGES: [(&str, &str); 82] = [
     ("badspext.pld", "Error in line 23: no suffix is allowed for SP\n"),
     ("badspusage.pld", "Error in line 21: use of SP is not allowed in equations\n"),
     ("badvcc.pld", "Error in line 4: pin 8 cannot be named VCC, because the name is reserved for pin 20\n"),
+    ("bus_bad.pld", "Error in line 4: bad bus, expected something like 'A[3..0]' or 'A[3:0]'\n"),
+    ("bus_bare.pld", "Error in line 7: a bus can only be compared with a constant or range, as in 'A[3..0] == 5'\n"),
+    ("bus_bit_high.pld", "Error in line 4: bus bits must be numbered from 0 to 63\n"),
+    ("bus_huge.pld", "Error in line 4: a bus can't be wider than 64 bits\n"),
+    ("bus_number.pld", "Error in line 7: bad number\n"),
+    ("bus_too_big.pld", "Error in line 7: 16 doesn't fit in a 4-bit bus\n"),
+    ("bus_wide.pld", "Error in line 7: a bus can't be wider than 20 bits\n"),
+    ("bus_wide_inputs.pld", "Error in line 3: a bus can't be wider than 20 bits\n"),
     ("continuation_bad.pld", "Error in line 12: unexpected token\n"),
+    ("directive_unknown.pld", "Error in line 3: unknown directive '%MODES'\n"),
+    ("extraclose.pld", "Error in line 7: unexpected token\n"),
     ("inputonly.pld", "Error in line 7: this pin can't be used as output\n"),
     ("logicgnd.pld", "Error in line 7: use of VCC and GND is not allowed in equations\n"),
     ("logicvcc.pld", "Error in line 7: use of VCC and GND is not allowed in equations\n"),
     ("longext.pld", "Error in line 7: unknown suffix found\n"),
+    ("macro_bad.pld", "Error in line 7: LET needs a plain name, as in 'LET X = A * B'\n"),
+    ("macro_repeated.pld", "Error in line 8: SEL is already defined\n"),
+    ("mode_bad.pld", "Error in line 3: bad argument for MODE\n"),
+    ("mode_complex_reg.pld", "Error in line 9: registered outputs aren't available in complex mode\n"),
+    ("mode_gal22v10.pld", "Error in line 3: MODE is not supported on this type of GAL\n"),
+    ("mode_pal.pld", "Error in line 4: MODE doesn't match the mode a PAL16L8 needs\n"),
+    ("mode_simple_feedback.pld", "Error in line 9: output pin 12 can't be used as input in simple mode\n"),
+    ("mode_simple_input.pld", "Error in line 8: pin 15 can't be used as input in simple mode\n"),
+    ("mode_simple_reg.pld", "Error in line 8: registered outputs aren't available in simple mode\n"),
+    ("mode_simple_tri.pld", "Error in line 8: tristate outputs aren't available in simple mode\n"),
     ("multiar.pld", "Error in line 23: only one product term allowed (no OR)\n"),
     ("multiclk.pld", "Error in line 22: only one product term allowed (no OR)\n"),
     ("multiena.pld", "Error in line 15: only one product term allowed (no OR)\n"),
@@ -183,6 +265,7 @@ const FAILURE_MESSAGES: [(&str, &str); 82] = [
     ("negsp.pld", "Error in line 25: negation of SP is not allowed\n"),
     ("negvcc.pld", "Error in line 7: VCC cannot be negated, use GND instead of /VCC\n"),
     ("noclk.pld", "Error in line 7: missing clock definition (.CLK) of registered output\n"),
+    ("noclose.pld", "Error in line 7: ')' expected\n"),
     ("noequals.pld", "Error in line 7: bad character in input\n"),
     ("nognd.pld", "Error in line 4: pin 10 must be named GND\n"),
     ("norhs.pld", "Error in line 7: unexpected end of file\n"),
@@ -194,9 +277,21 @@ const FAILURE_MESSAGES: [(&str, &str); 82] = [
     ("onlyenable.pld", "Error in line 10: the output must be defined to use .E\n"),
     ("onlyprst.pld", "Error in line 10: the output must be defined to use .APRST\n"),
     ("onlyrst.pld", "Error in line 10: the output must be defined to use .ARST\n"),
+    ("pal_active_high.pld", "Error in line 9: output must be active low to match a PAL20L8\n"),
+    ("pal_bad.pld", "Error in line 3: bad argument for TARGET\n"),
+    ("pal_combinatorial.pld", "Error in line 8: output can't be registered on a PAL16R4\n"),
+    ("pal_gal22v10.pld", "Error in line 3: TARGET is not supported on this type of GAL\n"),
+    ("pal_not_input.pld", "Error in line 10: pin 15 is a registered output on a PAL16R6, and can't be used as an input\n"),
+    ("pal_registered.pld", "Error in line 8: output must be registered to match a PAL16R4\n"),
+    ("pal_wrong_chip.pld", "Error in line 3: a PAL20R8 can only be replaced by a GAL20V8\n"),
     ("pinbadneg.pld", "Error in line 4: pinname expected after '/'\n"),
     ("pinrepeated.pld", "Error in line 4: pinname I5 is defined twice\n"),
+    ("place_bad.pld", "Error in line 4: bad argument for OUTPUTS\n"),
+    ("place_full.pld", "Error in line 4: no suitable pin left for X\n"),
     ("plaintri.pld", "Error in line 8: tristate control without previous '.T'\n"),
+    ("range_bad.pld", "Error in line 7: bad range, expected something like ':[C000..DFFF]'\n"),
+    ("range_misaligned.pld", "Error in line 7: range doesn't line up with bits 15 to 8 of the bus\n"),
+    ("range_too_many.pld", "Error in line 7: too many product terms in sum for pin (max: 8, saw: 14)\n"),
     ("regtri.pld", "Error in line 8: GAL16V8/20V8: tri. control for reg. output is not allowed\n"),
     ("repar.pld", "Error in line 25: AR is defined twice\n"),
     ("reparst.pld", "Error in line 26: multiple .APRST definitions for the same output\n"),
@@ -205,17 +300,26 @@ const FAILURE_MESSAGES: [(&str, &str); 82] = [
     ("reppin.pld", "Error in line 17: output O4 is defined multiple times\n"),
     ("reprst.pld", "Error in line 26: multiple .ARST definitions for the same output\n"),
     ("repsp.pld", "Error in line 25: SP is defined twice\n"),
+    ("sig_bad.pld", "Error in line 3: SIG needs up to 16 hex digits after '0x', up to 8 characters in quotes, or CRC\n"),
+    ("sig_long.pld", "Error in line 3: SIG needs up to 16 hex digits after '0x', up to 8 characters in quotes, or CRC\n"),
+    ("sig_noues.pld", "Error in line 4: SIG can't be used with UES OFF\n"),
 ("spbad.pld", "Error in line 5: GAL22V10: SP is not allowed as pinname\n"),
     ("threeline.pld", "Error in line 2: unexpected end of file\n"),
     ("toofewpins.pld", "Error in line 5: wrong number of pins\n"),
     ("toomanyterms_io.pld", "Error in line 7: too many product terms in sum for pin (max: 7, saw: 8)\n"),
     ("twoline.pld", "Error in line 2: unexpected end of file\n"),
+    ("ues_gal.pld", "Error in line 3: UES is not supported on this type of GAL\n"),
 ("unkext.pld", "Error in line 7: unknown suffix found\n"),
     ("unklhs.pld", "Error in line 17: unknown pinname\n"),
 ("unkpin.pld", "Error in line 9: unknown pinname\n"),
     ("unregclk.pld", "Error in line 11: use of .CLK is only allowed for registered outputs\n"),
     ("unregprst.pld", "Error in line 11: use of .APRST is only allowed for registered outputs\n"),
     ("unregrst.pld", "Error in line 11: use of .ARST is only allowed for registered outputs\n"),
+    ("vector_fail.pld", "Error in line 13: test vector failed on pin 18 (expected: H, saw: L)\n"),
+    ("vector_length.pld", "Error in line 13: wrong number of values in test vector (expected: 20, saw: 19)\n"),
+    ("vector_value.pld", "Error in line 13: bad value 'Q' in test vector (expected one of 0, 1, C, H, L, Z, X, N)\n"),
+    ("xor_chain.pld", "Error in line 7: expression expands to more than 528 product terms before minimisation (saw: 1024)\n"),
+    ("xorbig.pld", "Error in line 7: too many product terms in sum for pin (max: 8, saw: 16)\n"),
 ];
 
 #[test]
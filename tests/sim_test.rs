@@ -0,0 +1,257 @@
+//
+// sim_test.rs: Check the simulator against the source equations.
+//
+// The simulator only looks at the fuses, so by comparing it against
+// a direct evaluation of the equations we check both the simulator
+// and the assembler.
+//
+
+use std::collections::HashMap;
+
+use galette::{
+    blueprint::Blueprint,
+    gal_builder,
+    parser::{self, Content, Expr, Suffix, LHS},
+    sim::{Level, Simulator},
+};
+
+// Evaluate an expression, given values for the input pins and the
+// equations for the outputs, which may be fed back.
+fn eval_expr(expr: &Expr, inputs: &[bool], outputs: &HashMap<usize, &Expr>) -> bool {
+    match expr {
+        Expr::Pin(pin) => {
+            let value = match outputs.get(&pin.pin) {
+                Some(expr) => eval_expr(expr, inputs, outputs),
+                None => inputs[pin.pin - 1],
+            };
+            value != pin.neg
+        }
+        Expr::Not(expr) => !eval_expr(expr, inputs, outputs),
+        Expr::And(exprs) => exprs.iter().all(|e| eval_expr(e, inputs, outputs)),
+        Expr::Or(exprs) => exprs.iter().any(|e| eval_expr(e, inputs, outputs)),
+        Expr::Xor(lhs, rhs) => eval_expr(lhs, inputs, outputs) != eval_expr(rhs, inputs, outputs),
+        Expr::Range { pins, lo, hi } => {
+            let value = pins.iter().fold(0, |acc, pin| {
+                (acc << 1) | eval_expr(&Expr::Pin(*pin), inputs, outputs) as u64
+            });
+            (*lo..=*hi).contains(&value)
+        }
+    }
+}
+
+// Find the expected level on each output pin.
+fn expected_levels(content: &Content, inputs: &[bool]) -> HashMap<usize, Level> {
+    let mut outputs = HashMap::new();
+    let mut enables = HashMap::new();
+    for eqn in content.eqns.iter() {
+        match eqn.lhs {
+            LHS::Pin((pin, Suffix::None)) | LHS::Pin((pin, Suffix::T)) => {
+                outputs.insert(pin.pin, (pin.neg, &eqn.rhs));
+            }
+            LHS::Pin((pin, Suffix::E)) => {
+                enables.insert(pin.pin, &eqn.rhs);
+            }
+            _ => panic!("only combinatorial equations are supported"),
+        }
+    }
+
+    let output_exprs = outputs
+        .iter()
+        .map(|(pin, (_, expr))| (*pin, *expr))
+        .collect::<HashMap<_, _>>();
+
+    outputs
+        .iter()
+        .map(|(pin, (neg, expr))| {
+            let enabled = enables
+                .get(pin)
+                .is_none_or(|e| eval_expr(e, inputs, &output_exprs));
+            let level = if !enabled {
+                Level::HighZ
+            } else if eval_expr(expr, inputs, &output_exprs) != *neg {
+                Level::High
+            } else {
+                Level::Low
+            };
+            (*pin, level)
+        })
+        .collect()
+}
+
+fn check_file(file_name: &str) {
+    let content = parser::parse(file_name).unwrap();
+    let blueprint = Blueprint::from(&content).unwrap();
+    let gal = gal_builder::build(&blueprint).unwrap();
+    let sim = Simulator::new(&gal);
+
+    let num_pins = content.chip.num_pins();
+
+    // Drive every input pin that the equations use.
+    let mut input_pins = content
+        .eqns
+        .iter()
+        .flat_map(|eqn| eqn.rhs.pins())
+        .map(|pin| pin.pin)
+        .filter(|pin| pin % (num_pins / 2) != 0)
+        .collect::<Vec<usize>>();
+    input_pins.sort_unstable();
+    input_pins.dedup();
+
+    for bits in 0..1 << input_pins.len() {
+        let mut inputs = vec![false; num_pins];
+        for (i, pin) in input_pins.iter().enumerate() {
+            inputs[pin - 1] = bits & (1 << i) != 0;
+        }
+        // Hold /OE low on the GAL20RA10.
+        if content.chip == galette::chips::Chip::GAL20RA10 {
+            inputs[12] = false;
+        }
+
+        let levels = sim.eval(&inputs).unwrap();
+        for (pin, level) in expected_levels(&content, &inputs) {
+            assert_eq!(
+                levels[pin - 1],
+                level,
+                "{}: pin {} with inputs {:?}",
+                file_name,
+                pin,
+                inputs
+            );
+        }
+    }
+}
+
+#[test]
+fn test_combinatorial() {
+    for name in [
+        "GAL16V8_combinatorial",
+        "GAL16V8_complex_feedback",
+        "GAL16V8_tri",
+        "GAL20V8_combinatorial",
+        "GAL20V8_complex",
+        "GAL20V8_tri",
+        "GAL22V10_combinatorial",
+        "GAL22V10_tri",
+        "GAL20RA10_combinatorial",
+        "GAL20RA10_tri",
+        "macros",
+        "negpin",
+        "parens",
+        "ranges",
+        "xor",
+    ] {
+        check_file(&format!("testcases/success/{}.pld", name));
+    }
+}
+
+#[test]
+fn test_unstable() {
+    let content = parser::parse("testcases/success/GAL16V8_complex_feedback.pld").unwrap();
+    let mut blueprint = Blueprint::from(&content).unwrap();
+
+    // Turn O1 into an inverter of itself, which oscillates.
+    let (_, ref mut term) = blueprint.olmcs[1].output.as_mut().unwrap();
+    term.pins = vec![vec![galette::gal::Pin { pin: 13, neg: true }]];
+
+    let gal = gal_builder::build(&blueprint).unwrap();
+    assert!(Simulator::new(&gal).eval(&[false; 20]).is_err());
+}
+
+fn build(file_name: &str) -> galette::gal::GAL {
+    let content = parser::parse(file_name).unwrap();
+    let blueprint = Blueprint::from(&content).unwrap();
+    gal_builder::build(&blueprint).unwrap()
+}
+
+// Make an input vector with the given pins high.
+fn inputs(num_pins: usize, high: &[usize]) -> Vec<bool> {
+    (1..=num_pins).map(|pin| high.contains(&pin)).collect()
+}
+
+#[test]
+fn test_registered_galxv8() {
+    let gal = build("testcases/success/GAL16V8_reg.pld");
+    let mut sim = Simulator::new(&gal);
+
+    // O1.R = I2 + I3, on pin 13. I2 is pin 4. Registers are reset at
+    // power-up, and the outputs come from /Q.
+    let levels = sim.step(&inputs(20, &[])).unwrap();
+    assert_eq!(levels[12], Level::High);
+    let levels = sim.step(&inputs(20, &[1])).unwrap();
+    assert_eq!(levels[12], Level::Low);
+    // Clock in a one.
+    sim.step(&inputs(20, &[4])).unwrap();
+    let levels = sim.step(&inputs(20, &[1, 4])).unwrap();
+    assert_eq!(levels[12], Level::High);
+    // It holds until the next rising edge.
+    let levels = sim.step(&inputs(20, &[])).unwrap();
+    assert_eq!(levels[12], Level::High);
+    let levels = sim.step(&inputs(20, &[1])).unwrap();
+    assert_eq!(levels[12], Level::Low);
+
+    // Registered outputs are disabled by /OE on pin 11.
+    let levels = sim.step(&inputs(20, &[1, 11])).unwrap();
+    assert_eq!(levels[12], Level::HighZ);
+}
+
+#[test]
+fn test_registered_gal22v10() {
+    let gal = build("testcases/success/GAL22V10_arsp.pld");
+    let mut sim = Simulator::new(&gal);
+
+    // At power-up, registers are reset. O0 (pin 14) is active high,
+    // and /O4 (pin 18) is active low.
+    let levels = sim.step(&inputs(24, &[])).unwrap();
+    assert_eq!(levels[13], Level::Low);
+    assert_eq!(levels[17], Level::High);
+
+    // SP = I1 (pin 3) presets all registers on the clock.
+    sim.step(&inputs(24, &[3])).unwrap();
+    let levels = sim.step(&inputs(24, &[1, 3])).unwrap();
+    assert_eq!(levels[13], Level::High);
+    assert_eq!(levels[17], Level::Low);
+
+    // AR = I0 (pin 2) resets them without a clock.
+    let levels = sim.step(&inputs(24, &[1, 2])).unwrap();
+    assert_eq!(levels[13], Level::Low);
+    assert_eq!(levels[17], Level::High);
+
+    // /O5.R = /I7 + I8 on pin 20, and O6.R = O5 on pin 21. O5 is fed
+    // back from /Q, and galette flips the sense of registered
+    // feedback, so O6 follows the register contents rather than the
+    // pin, which is inverted.
+    sim.step(&inputs(24, &[])).unwrap();
+    let levels = sim.step(&inputs(24, &[1])).unwrap();
+    assert_eq!(levels[19], Level::Low);
+    assert_eq!(levels[20], Level::Low);
+    sim.step(&inputs(24, &[])).unwrap();
+    let levels = sim.step(&inputs(24, &[1])).unwrap();
+    assert_eq!(levels[19], Level::Low);
+    assert_eq!(levels[20], Level::High);
+}
+
+#[test]
+fn test_registered_gal20ra10() {
+    let gal = build("testcases/success/GAL20RA10_rst.pld");
+    let mut sim = Simulator::new(&gal);
+
+    // O7.R = I7 on pin 21, clocked by /Clock (pin 11). I7 is pin 9.
+    sim.step(&inputs(24, &[9, 11])).unwrap();
+    let levels = sim.step(&inputs(24, &[9])).unwrap();
+    assert_eq!(levels[20], Level::High);
+    let levels = sim.step(&inputs(24, &[11])).unwrap();
+    assert_eq!(levels[20], Level::High);
+    let levels = sim.step(&inputs(24, &[])).unwrap();
+    assert_eq!(levels[20], Level::Low);
+
+    // /O5.R on pin 19 has O5.ARST = I0 (pin 2) and O5.APRST = I1
+    // (pin 3), which act without a clock.
+    let levels = sim.step(&inputs(24, &[3])).unwrap();
+    assert_eq!(levels[18], Level::Low);
+    let levels = sim.step(&inputs(24, &[2])).unwrap();
+    assert_eq!(levels[18], Level::High);
+
+    // The outputs are disabled by /OE on pin 13.
+    let levels = sim.step(&inputs(24, &[13])).unwrap();
+    assert_eq!(levels[18], Level::HighZ);
+}